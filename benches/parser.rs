@@ -0,0 +1,40 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use htmlparser::{HtmlParser, HtmlEvent};
+
+const FIXTURES: [&str; 2] = ["tests/htmls/complex.html", "tests/htmls/complex2.html"];
+const MINIMUM_DURATION: Duration = Duration::from_secs(2);
+
+fn parse(source: &[u8], borrowed: bool) -> usize {
+    let mut parser = if borrowed { HtmlParser::from_bytes(source) } else { HtmlParser::new(source) };
+    let mut events = 0;
+
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::HtmlDocumentEnd => return events,
+            _ => events += 1
+        }
+    }
+}
+
+fn main() {
+    for (fixture, borrowed) in FIXTURES.iter().flat_map(|fixture| [(fixture, false), (fixture, true)]) {
+        let source = fs::read(fixture).unwrap();
+        let events = parse(&source, borrowed);
+
+        let mut iterations = 0;
+        let started = Instant::now();
+        while iterations == 0 || started.elapsed() < MINIMUM_DURATION {
+            assert_eq!(parse(&source, borrowed), events);
+            iterations += 1;
+        }
+
+        let elapsed = started.elapsed() / iterations;
+        let throughput = source.len() as f64 / elapsed.as_secs_f64() / (1024.0 * 1024.0);
+        println!(
+            "{:<28} {:<8} {:>8} bytes {:>7} events {:>10.3?}/iter {:>8.2} MiB/s",
+            fixture, if borrowed { "borrowed" } else { "reader" }, source.len(), events, elapsed, throughput
+        );
+    }
+}
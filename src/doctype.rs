@@ -0,0 +1,196 @@
+#![allow(clippy::needless_return)]
+
+/// Document mode chosen from the doctype, see the HTML standard's
+/// "quirks mode" and "limited-quirks mode".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuirksMode {
+    #[default]
+    NoQuirks,
+    LimitedQuirks,
+    Quirks
+}
+
+/// Public identifier prefixes that put a document into quirks mode.
+const QUIRKS_PUBLIC_PREFIXES: &[&str] = &[
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html 2.0 level 1//",
+    "-//ietf//dtd html 2.0 level 2//",
+    "-//ietf//dtd html 2.0 strict level 1//",
+    "-//ietf//dtd html 2.0 strict level 2//",
+    "-//ietf//dtd html 2.0 strict//",
+    "-//ietf//dtd html 2.0//",
+    "-//ietf//dtd html 2.1e//",
+    "-//ietf//dtd html 3.0//",
+    "-//ietf//dtd html 3.2 final//",
+    "-//ietf//dtd html 3.2//",
+    "-//ietf//dtd html 3//",
+    "-//ietf//dtd html level 0//",
+    "-//ietf//dtd html level 1//",
+    "-//ietf//dtd html level 2//",
+    "-//ietf//dtd html level 3//",
+    "-//ietf//dtd html strict level 0//",
+    "-//ietf//dtd html strict level 1//",
+    "-//ietf//dtd html strict level 2//",
+    "-//ietf//dtd html strict level 3//",
+    "-//ietf//dtd html strict//",
+    "-//ietf//dtd html//",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer 2.0 html strict//",
+    "-//microsoft//dtd internet explorer 2.0 html//",
+    "-//microsoft//dtd internet explorer 2.0 tables//",
+    "-//microsoft//dtd internet explorer 3.0 html strict//",
+    "-//microsoft//dtd internet explorer 3.0 html//",
+    "-//microsoft//dtd internet explorer 3.0 tables//",
+    "-//netscape comm. corp.//dtd html//",
+    "-//netscape comm. corp.//dtd strict html//",
+    "-//o'reilly and associates//dtd html 2.0//",
+    "-//o'reilly and associates//dtd html extended 1.0//",
+    "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+    "-//softquad//dtd hotmetal pro 4.0::19970916::extensions to html 4.0//",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sun microsystems corp.//dtd hotjava html//",
+    "-//sun microsystems corp.//dtd hotjava strict html//",
+    "-//w3c//dtd html 3 1995-03-24//",
+    "-//w3c//dtd html 3.2 draft//",
+    "-//w3c//dtd html 3.2 final//",
+    "-//w3c//dtd html 3.2//",
+    "-//w3c//dtd html 3.2s draft//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental 19960712//",
+    "-//w3c//dtd html experimental 970421//",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html 2.0//",
+    "-//webtechs//dtd mozilla html//"
+];
+
+/// HTML 4.01 public identifier prefixes, quirks without a system identifier
+/// and limited quirks with one.
+const HTML_401_PUBLIC_PREFIXES: &[&str] = &["-//w3c//dtd html 4.01 frameset//", "-//w3c//dtd html 4.01 transitional//"];
+
+const LIMITED_QUIRKS_PUBLIC_PREFIXES: &[&str] = &["-//w3c//dtd xhtml 1.0 frameset//", "-//w3c//dtd xhtml 1.0 transitional//"];
+
+/// Doctype token, the text of a `<!DOCTYPE ...>` declaration taken apart.
+#[derive(Debug, Default)]
+pub(crate) struct Doctype {
+    pub(crate) name: Option<String>,
+    pub(crate) public_id: Option<String>,
+    pub(crate) system_id: Option<String>,
+    pub(crate) force_quirks: bool
+}
+
+impl Doctype {
+    /// Parses the declaration text following `<!DOCTYPE`, up to but not
+    /// including its `>`.
+    pub(crate) fn parse(text: &str) -> Doctype {
+        let mut doctype = Doctype::default();
+        let rest = text.trim_start_matches(is_whitespace);
+        if rest.is_empty() {
+            doctype.force_quirks = true;
+
+            return doctype;
+        }
+
+        let name_length = rest.find(is_whitespace).unwrap_or(rest.len());
+        doctype.name = Some(rest[0..name_length].to_ascii_lowercase().replace('\0', "\u{fffd}"));
+
+        let rest = rest[name_length..].trim_start_matches(is_whitespace);
+        if rest.is_empty() {
+            return doctype;
+        }
+
+        let keyword = rest.get(0..6).map(|keyword| keyword.to_ascii_lowercase());
+        let rest = &rest[keyword.as_ref().map_or(0, |keyword| keyword.len())..];
+        match keyword.as_deref() {
+            Some("public") => {
+                let Some(rest) = doctype.read_identifier(rest, true) else {
+                    return doctype;
+                };
+
+                if !rest.is_empty() {
+                    doctype.read_identifier(rest, false);
+                }
+            }
+
+            Some("system") => {
+                doctype.read_identifier(rest, false);
+            }
+
+            _ => doctype.force_quirks = true
+        }
+
+        return doctype;
+    }
+
+    /// Reads a quoted identifier into the public or system identifier and
+    /// returns the text after it. Identifiers that are not quoted make the
+    /// doctype bogus, identifiers without a closing quote force quirks mode.
+    fn read_identifier<'t>(&mut self, text: &'t str, is_public: bool) -> Option<&'t str> {
+        let text = text.trim_start_matches(is_whitespace);
+        let Some(quote) = text.chars().next().filter(|char| *char == '"' || *char == '\'') else {
+            self.force_quirks = true;
+
+            return None;
+        };
+
+        let text = &text[1..];
+        let (identifier, rest) = match text.find(quote) {
+            Some(end) => (&text[0..end], Some(text[end + 1..].trim_start_matches(is_whitespace))),
+            None => {
+                self.force_quirks = true;
+                (text, None)
+            }
+        };
+
+        let identifier = Some(identifier.replace('\0', "\u{fffd}"));
+        if is_public {
+            self.public_id = identifier;
+        } else {
+            self.system_id = identifier;
+            if rest.is_some_and(|rest| !rest.is_empty()) {
+                // Trailing garbage makes the doctype bogus without forcing
+                // quirks mode.
+                return None;
+            }
+        }
+
+        return rest;
+    }
+}
+
+fn is_whitespace(char: char) -> bool {
+    return matches!(char, '\t' | '\n' | '\x0c' | '\r' | ' ');
+}
+
+/// Document mode for a doctype as described by the "initial" insertion mode
+/// of the HTML standard.
+pub fn quirks_mode(name: Option<&str>, public_id: Option<&str>, system_id: Option<&str>, force_quirks: bool) -> QuirksMode {
+    if force_quirks || name != Some("html") {
+        return QuirksMode::Quirks;
+    }
+
+    let public_id = public_id.map(|id| id.to_ascii_lowercase());
+    let system_id = system_id.map(|id| id.to_ascii_lowercase());
+    let public_starts_with = |prefixes: &[&str]| {
+        public_id.as_deref().is_some_and(|id| prefixes.iter().any(|prefix| id.starts_with(prefix)))
+    };
+
+    if matches!(public_id.as_deref(), Some("-//w3o//dtd w3 html strict 3.0//en//" | "-/w3c/dtd html 4.0 transitional/en" | "html"))
+        || system_id.as_deref() == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || public_starts_with(QUIRKS_PUBLIC_PREFIXES)
+        || (system_id.is_none() && public_starts_with(HTML_401_PUBLIC_PREFIXES))
+    {
+        return QuirksMode::Quirks;
+    }
+
+    if public_starts_with(LIMITED_QUIRKS_PUBLIC_PREFIXES) || (system_id.is_some() && public_starts_with(HTML_401_PUBLIC_PREFIXES)) {
+        return QuirksMode::LimitedQuirks;
+    }
+
+    return QuirksMode::NoQuirks;
+}
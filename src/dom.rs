@@ -0,0 +1,229 @@
+#![allow(clippy::needless_return)]
+
+use std::borrow::Cow;
+use std::io::Read;
+
+use crate::element::{HtmlElement, HtmlElementName};
+use crate::error::ParseError;
+use crate::stack::ElementStack;
+use crate::{HtmlEvent, HtmlParser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+pub enum NodeData<'a> {
+    Document,
+    Doctype { name: Cow<'a, str>, public_id: Option<Cow<'a, str>>, system_id: Option<Cow<'a, str>> },
+    Element(HtmlElement<'a>),
+    Text(Cow<'a, str>),
+    Comment(Cow<'a, str>)
+}
+
+#[derive(Debug, Clone)]
+pub struct Node<'a> {
+    pub data: NodeData<'a>,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>
+}
+
+impl<'a> Node<'a> {
+    fn new(data: NodeData<'a>) -> Node<'a> {
+        return Node {
+            data,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None
+        };
+    }
+
+    pub fn element(&self) -> Option<&HtmlElement<'a>> {
+        return match &self.data {
+            NodeData::Element(element) => Some(element),
+            _ => None
+        };
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        return self.parent;
+    }
+
+    pub fn first_child(&self) -> Option<NodeId> {
+        return self.first_child;
+    }
+
+    pub fn last_child(&self) -> Option<NodeId> {
+        return self.last_child;
+    }
+
+    pub fn previous_sibling(&self) -> Option<NodeId> {
+        return self.previous_sibling;
+    }
+
+    pub fn next_sibling(&self) -> Option<NodeId> {
+        return self.next_sibling;
+    }
+}
+
+/// Open element of the tree builder, mirrors the parser's own stack entries.
+struct OpenElement {
+    node: NodeId,
+    name: HtmlElementName
+}
+
+impl AsRef<HtmlElementName> for OpenElement {
+    fn as_ref(&self) -> &HtmlElementName {
+        return &self.name;
+    }
+}
+
+/// Arena-backed document tree, every node is addressed by its `NodeId`.
+#[derive(Debug, Clone)]
+pub struct Document<'a> {
+    nodes: Vec<Node<'a>>
+}
+
+impl<'a> Document<'a> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &'a str) -> Result<Document<'a>, ParseError> {
+        return Document::parse(HtmlParser::from_str(source));
+    }
+
+    /// Builds the tree from the parser's events. Elements are nested exactly
+    /// as the parser's open-element stack nests them, the first error returned
+    /// by the parser aborts the build.
+    pub fn parse<R: Read>(parser: HtmlParser<'a, R>) -> Result<Document<'a>, ParseError> {
+        let mut document = Document { nodes: vec![Node::new(NodeData::Document)] };
+        let mut elements: ElementStack<OpenElement> = ElementStack::new();
+
+        for event in parser {
+            let parent = elements.last().map_or(document.root(), |element| element.node);
+            match event? {
+                HtmlEvent::Doctype { name, public_id, system_id, .. } => {
+                    document.append(parent, NodeData::Doctype { name: name.unwrap_or_default(), public_id, system_id });
+                }
+
+                HtmlEvent::HtmlElementOpened { opened_element } => {
+                    let name = opened_element.name.clone();
+                    let node = document.append(parent, NodeData::Element(opened_element));
+                    elements.push(OpenElement { node, name });
+                }
+
+                HtmlEvent::HtmlElementClosed { closed_element } => {
+                    elements.pop(&closed_element.name);
+                }
+
+                HtmlEvent::TextContent { content, .. } => {
+                    document.append(parent, NodeData::Text(content));
+                }
+
+                HtmlEvent::Comment { content, .. } => {
+                    document.append(parent, NodeData::Comment(content));
+                }
+
+                HtmlEvent::HtmlDocumentEnd => {
+                    break;
+                }
+            }
+        }
+
+        return Ok(document);
+    }
+
+    fn append(&mut self, parent: NodeId, data: NodeData<'a>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        let mut node = Node::new(data);
+        node.parent = Some(parent);
+        node.previous_sibling = self.nodes[parent.0].last_child;
+        self.nodes.push(node);
+
+        if let Some(previous_sibling) = self.nodes[parent.0].last_child {
+            self.nodes[previous_sibling.0].next_sibling = Some(id);
+        } else {
+            self.nodes[parent.0].first_child = Some(id);
+        }
+
+        self.nodes[parent.0].last_child = Some(id);
+
+        return id;
+    }
+
+    pub fn root(&self) -> NodeId {
+        return NodeId(0);
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<'a> {
+        return &self.nodes[id.0];
+    }
+
+    pub fn children(&self, id: NodeId) -> Children<'_, 'a> {
+        return Children { document: self, next: self.node(id).first_child };
+    }
+
+    /// All nodes below `id` in document order, `id` itself excluded.
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_, 'a> {
+        return Descendants { document: self, root: id, next: self.node(id).first_child };
+    }
+
+    /// Concatenated text of all text nodes below `id`, like the DOM `textContent`.
+    pub fn text_content(&self, id: NodeId) -> String {
+        let texts: Vec<&str> = self.descendants(id)
+            .filter_map(|node| match &self.node(node).data {
+                NodeData::Text(text) => Some(text.as_ref()),
+                _ => None
+            })
+            .collect();
+
+        return texts.concat();
+    }
+}
+
+pub struct Children<'d, 'a> {
+    document: &'d Document<'a>,
+    next: Option<NodeId>
+}
+
+impl Iterator for Children<'_, '_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.node(current).next_sibling;
+
+        return Some(current);
+    }
+}
+
+pub struct Descendants<'d, 'a> {
+    document: &'d Document<'a>,
+    root: NodeId,
+    next: Option<NodeId>
+}
+
+impl Iterator for Descendants<'_, '_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        let node = self.document.node(current);
+        self.next = node.first_child;
+
+        let mut ancestor = Some(current);
+        while self.next.is_none() {
+            let Some(id) = ancestor.filter(|id| *id != self.root) else {
+                break;
+            };
+
+            let node = self.document.node(id);
+            self.next = node.next_sibling;
+            ancestor = node.parent;
+        }
+
+        return Some(current);
+    }
+}
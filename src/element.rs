@@ -1,5 +1,10 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+use crate::position::{AttributeSpan, Span};
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum HtmlElementName {
@@ -140,7 +145,8 @@ pub enum HtmlElementName {
     Strike,
     Tt,
     Xmp,
-    Doctype
+    Doctype,
+    Custom(String)
 }
 
 impl HtmlElementName {
@@ -283,12 +289,20 @@ impl HtmlElementName {
             HtmlElementName::Strike => "strike",
             HtmlElementName::Tt => "tt",
             HtmlElementName::Xmp => "xmp",
-            HtmlElementName::Doctype => "!doctype"
+            HtmlElementName::Doctype => "!doctype",
+            HtmlElementName::Custom(name) => name.as_str()
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(value: &str) -> Result<HtmlElementName, &'static str> {
-        match value.to_string().to_lowercase().as_str() {
+        let name = if value.chars().any(char::is_uppercase) {
+            Cow::Owned(value.to_lowercase())
+        } else {
+            Cow::Borrowed(value)
+        };
+
+        match name.as_ref() {
             "html" => Ok(HtmlElementName::Html),
             "base" => Ok(HtmlElementName::Base),
             "head" => Ok(HtmlElementName::Head),
@@ -427,6 +441,7 @@ impl HtmlElementName {
             "tt" => Ok(HtmlElementName::Tt),
             "xmp" => Ok(HtmlElementName::Xmp),
             "!doctype" => Ok(HtmlElementName::Doctype),
+            _ if name.starts_with(|c: char| c.is_ascii_alphabetic()) => Ok(HtmlElementName::Custom(name.into_owned())),
             _ => Err("invalid element")
         }
     }
@@ -435,6 +450,7 @@ impl HtmlElementName {
         return self.to_str() == element.to_str();
     }
 
+    #[allow(clippy::match_like_matches_macro)]
     pub fn is_self_closing_element(&self) -> bool {
         match self {
             HtmlElementName::Area
@@ -458,8 +474,132 @@ impl HtmlElementName {
     }
 }
 
+/// Namespace of an element or an attribute of a foreign element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Namespace {
+    #[default]
+    Html,
+    Svg,
+    MathMl,
+    XLink,
+    Xml,
+    Xmlns
+}
+
+impl Namespace {
+    pub fn url(&self) -> &'static str {
+        return match self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+            Namespace::XLink => "http://www.w3.org/1999/xlink",
+            Namespace::Xml => "http://www.w3.org/XML/1998/namespace",
+            Namespace::Xmlns => "http://www.w3.org/2000/xmlns/"
+        };
+    }
+}
+
+/// SVG element names the tokenizer lowercases, with their proper case.
+const SVG_ELEMENT_NAMES: [(&str, &str); 37] = [
+    ("altglyph", "altGlyph"), ("altglyphdef", "altGlyphDef"), ("altglyphitem", "altGlyphItem"),
+    ("animatecolor", "animateColor"), ("animatemotion", "animateMotion"), ("animatetransform", "animateTransform"),
+    ("clippath", "clipPath"), ("feblend", "feBlend"), ("fecolormatrix", "feColorMatrix"),
+    ("fecomponenttransfer", "feComponentTransfer"), ("fecomposite", "feComposite"), ("feconvolvematrix", "feConvolveMatrix"),
+    ("fediffuselighting", "feDiffuseLighting"), ("fedisplacementmap", "feDisplacementMap"), ("fedistantlight", "feDistantLight"),
+    ("fedropshadow", "feDropShadow"), ("feflood", "feFlood"), ("fefunca", "feFuncA"), ("fefuncb", "feFuncB"),
+    ("fefuncg", "feFuncG"), ("fefuncr", "feFuncR"), ("fegaussianblur", "feGaussianBlur"), ("feimage", "feImage"),
+    ("femerge", "feMerge"), ("femergenode", "feMergeNode"), ("femorphology", "feMorphology"), ("feoffset", "feOffset"),
+    ("fepointlight", "fePointLight"), ("fespecularlighting", "feSpecularLighting"), ("fespotlight", "feSpotLight"),
+    ("fetile", "feTile"), ("feturbulence", "feTurbulence"), ("foreignobject", "foreignObject"), ("glyphref", "glyphRef"),
+    ("lineargradient", "linearGradient"), ("radialgradient", "radialGradient"), ("textpath", "textPath")
+];
+
+/// SVG attribute names the tokenizer lowercases, with their proper case.
+const SVG_ATTRIBUTE_NAMES: [(&str, &str); 58] = [
+    ("attributename", "attributeName"), ("attributetype", "attributeType"), ("basefrequency", "baseFrequency"),
+    ("baseprofile", "baseProfile"), ("calcmode", "calcMode"), ("clippathunits", "clipPathUnits"),
+    ("diffuseconstant", "diffuseConstant"), ("edgemode", "edgeMode"), ("filterunits", "filterUnits"), ("glyphref", "glyphRef"),
+    ("gradienttransform", "gradientTransform"), ("gradientunits", "gradientUnits"), ("kernelmatrix", "kernelMatrix"),
+    ("kernelunitlength", "kernelUnitLength"), ("keypoints", "keyPoints"), ("keysplines", "keySplines"), ("keytimes", "keyTimes"),
+    ("lengthadjust", "lengthAdjust"), ("limitingconeangle", "limitingConeAngle"), ("markerheight", "markerHeight"),
+    ("markerunits", "markerUnits"), ("markerwidth", "markerWidth"), ("maskcontentunits", "maskContentUnits"),
+    ("maskunits", "maskUnits"), ("numoctaves", "numOctaves"), ("pathlength", "pathLength"),
+    ("patterncontentunits", "patternContentUnits"), ("patterntransform", "patternTransform"), ("patternunits", "patternUnits"),
+    ("pointsatx", "pointsAtX"), ("pointsaty", "pointsAtY"), ("pointsatz", "pointsAtZ"), ("preservealpha", "preserveAlpha"),
+    ("preserveaspectratio", "preserveAspectRatio"), ("primitiveunits", "primitiveUnits"), ("refx", "refX"), ("refy", "refY"),
+    ("repeatcount", "repeatCount"), ("repeatdur", "repeatDur"), ("requiredextensions", "requiredExtensions"),
+    ("requiredfeatures", "requiredFeatures"), ("specularconstant", "specularConstant"), ("specularexponent", "specularExponent"),
+    ("spreadmethod", "spreadMethod"), ("startoffset", "startOffset"), ("stddeviation", "stdDeviation"),
+    ("stitchtiles", "stitchTiles"), ("surfacescale", "surfaceScale"), ("systemlanguage", "systemLanguage"),
+    ("tablevalues", "tableValues"), ("targetx", "targetX"), ("targety", "targetY"), ("textlength", "textLength"),
+    ("viewbox", "viewBox"), ("viewtarget", "viewTarget"), ("xchannelselector", "xChannelSelector"),
+    ("ychannelselector", "yChannelSelector"), ("zoomandpan", "zoomAndPan")
+];
+
+const MATHML_ATTRIBUTE_NAMES: [(&str, &str); 1] = [("definitionurl", "definitionURL")];
+
 #[derive(Debug, Clone)]
-pub struct HtmlElement {
+pub struct HtmlElement<'a> {
     pub name: HtmlElementName,
-    pub attributes: HashMap<String, String>
+    /// `Svg` and `MathMl` for elements of foreign content.
+    pub namespace: Namespace,
+    pub attributes: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    pub self_closing: bool,
+    /// Source span of the tag, from `<` to `>` inclusive.
+    pub span: Span,
+    pub attribute_spans: HashMap<Cow<'a, str>, AttributeSpan>,
+    /// Number of elements the element is nested in.
+    pub depth: usize
+}
+
+impl<'a> HtmlElement<'a> {
+    /// Namespace of an attribute, only the `xlink:`, `xml:` and `xmlns`
+    /// attributes of foreign elements have one.
+    pub fn attribute_namespace(&self, name: &str) -> Option<Namespace> {
+        if self.namespace == Namespace::Html {
+            return None;
+        }
+
+        return match name {
+            "xlink:actuate" | "xlink:arcrole" | "xlink:href" | "xlink:role" | "xlink:show" | "xlink:title" | "xlink:type" => Some(Namespace::XLink),
+            "xml:lang" | "xml:space" => Some(Namespace::Xml),
+            "xmlns" | "xmlns:xlink" => Some(Namespace::Xmlns),
+            _ => None
+        };
+    }
+
+    /// Restores the case of foreign element and attribute names, the
+    /// tokenizer lowercases them.
+    pub(crate) fn adjust_foreign_names(&mut self) -> () {
+        let attribute_names: &[(&str, &'static str)] = match self.namespace {
+            Namespace::Svg => &SVG_ATTRIBUTE_NAMES,
+            Namespace::MathMl => &MATHML_ATTRIBUTE_NAMES,
+            _ => return
+        };
+
+        if self.namespace == Namespace::Svg {
+            if let Some(name) = adjusted_name(&SVG_ELEMENT_NAMES, self.name.to_str()) {
+                self.name = HtmlElementName::Custom(name.to_string());
+            }
+        }
+
+        let renamed: Vec<(Cow<'a, str>, &'static str)> = self.attributes
+            .keys()
+            .filter_map(|name| Some((name.clone(), adjusted_name(attribute_names, name)?)))
+            .collect();
+
+        for (name, adjusted) in renamed {
+            if let Some(value) = self.attributes.remove(&name) {
+                self.attributes.insert(Cow::Borrowed(adjusted), value);
+            }
+
+            if let Some(span) = self.attribute_spans.remove(&name) {
+                self.attribute_spans.insert(Cow::Borrowed(adjusted), span);
+            }
+        }
+    }
+}
+
+fn adjusted_name(names: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    return names.iter().find(|(lowercase, _)| *lowercase == name).map(|(_, adjusted)| *adjusted);
 }
@@ -0,0 +1,393 @@
+#![allow(clippy::needless_return)]
+
+/// Character encodings the parser can decode, named and labelled as in the
+/// WHATWG Encoding Standard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1250,
+    Windows1252,
+    Iso8859_2
+}
+
+/// How sure the parser is about the encoding it decodes the source with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// Guessed from a `<meta>` prescan or the UTF-8 default.
+    Tentative,
+    /// Given by a byte order mark or the transport layer.
+    Certain,
+    /// The source was handed over as text and needs no decoding.
+    Irrelevant
+}
+
+/// Handling of invalid byte sequences in UTF-8 sources. Inserted characters
+/// count with their UTF-8 length in spans, so offsets behind them no longer
+/// match the source bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidUtf8 {
+    /// Decode each invalid sequence as U+FFFD, like browsers do.
+    #[default]
+    Replace,
+    /// Drop the sequence and report `ParseError::InvalidUtf8` at its offset,
+    /// parsing continues behind it.
+    Error,
+    /// Decode the bytes of the sequence as ISO-8859-1, one character each.
+    Latin1
+}
+
+/// Number of bytes the `<meta>` prescan looks at.
+pub(crate) const PRESCAN_LENGTH: usize = 1024;
+
+/// Code points of the bytes 0x80 to 0xff.
+type HighHalf = [u16; 128];
+
+/// Identity mapping of the C1 controls 0x80 to 0x9f.
+const C1_CONTROLS: [u16; 32] = [
+    0x0080, 0x0081, 0x0082, 0x0083, 0x0084, 0x0085, 0x0086, 0x0087, 0x0088, 0x0089, 0x008a, 0x008b, 0x008c, 0x008d, 0x008e, 0x008f,
+    0x0090, 0x0091, 0x0092, 0x0093, 0x0094, 0x0095, 0x0096, 0x0097, 0x0098, 0x0099, 0x009a, 0x009b, 0x009c, 0x009d, 0x009e, 0x009f
+];
+
+/// Bytes 0xc0 to 0xff, shared by ISO-8859-2 and windows-1250.
+const LATIN2_LETTERS: [u16; 64] = [
+    0x0154, 0x00c1, 0x00c2, 0x0102, 0x00c4, 0x0139, 0x0106, 0x00c7, 0x010c, 0x00c9, 0x0118, 0x00cb, 0x011a, 0x00cd, 0x00ce, 0x010e,
+    0x0110, 0x0143, 0x0147, 0x00d3, 0x00d4, 0x0150, 0x00d6, 0x00d7, 0x0158, 0x016e, 0x00da, 0x0170, 0x00dc, 0x00dd, 0x0162, 0x00df,
+    0x0155, 0x00e1, 0x00e2, 0x0103, 0x00e4, 0x013a, 0x0107, 0x00e7, 0x010d, 0x00e9, 0x0119, 0x00eb, 0x011b, 0x00ed, 0x00ee, 0x010f,
+    0x0111, 0x0144, 0x0148, 0x00f3, 0x00f4, 0x0151, 0x00f6, 0x00f7, 0x0159, 0x016f, 0x00fa, 0x0171, 0x00fc, 0x00fd, 0x0163, 0x02d9
+];
+
+const WINDOWS_1252: HighHalf = concat([
+    0x20ac, 0x0081, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160, 0x2039, 0x0152, 0x008d, 0x017d, 0x008f,
+    0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014, 0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x009d, 0x017e, 0x0178
+], [
+    0x00a0, 0x00a1, 0x00a2, 0x00a3, 0x00a4, 0x00a5, 0x00a6, 0x00a7, 0x00a8, 0x00a9, 0x00aa, 0x00ab, 0x00ac, 0x00ad, 0x00ae, 0x00af,
+    0x00b0, 0x00b1, 0x00b2, 0x00b3, 0x00b4, 0x00b5, 0x00b6, 0x00b7, 0x00b8, 0x00b9, 0x00ba, 0x00bb, 0x00bc, 0x00bd, 0x00be, 0x00bf
+], [
+    0x00c0, 0x00c1, 0x00c2, 0x00c3, 0x00c4, 0x00c5, 0x00c6, 0x00c7, 0x00c8, 0x00c9, 0x00ca, 0x00cb, 0x00cc, 0x00cd, 0x00ce, 0x00cf,
+    0x00d0, 0x00d1, 0x00d2, 0x00d3, 0x00d4, 0x00d5, 0x00d6, 0x00d7, 0x00d8, 0x00d9, 0x00da, 0x00db, 0x00dc, 0x00dd, 0x00de, 0x00df,
+    0x00e0, 0x00e1, 0x00e2, 0x00e3, 0x00e4, 0x00e5, 0x00e6, 0x00e7, 0x00e8, 0x00e9, 0x00ea, 0x00eb, 0x00ec, 0x00ed, 0x00ee, 0x00ef,
+    0x00f0, 0x00f1, 0x00f2, 0x00f3, 0x00f4, 0x00f5, 0x00f6, 0x00f7, 0x00f8, 0x00f9, 0x00fa, 0x00fb, 0x00fc, 0x00fd, 0x00fe, 0x00ff
+]);
+
+const WINDOWS_1250: HighHalf = concat([
+    0x20ac, 0x0081, 0x201a, 0x0083, 0x201e, 0x2026, 0x2020, 0x2021, 0x0088, 0x2030, 0x0160, 0x2039, 0x015a, 0x0164, 0x017d, 0x0179,
+    0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014, 0x0098, 0x2122, 0x0161, 0x203a, 0x015b, 0x0165, 0x017e, 0x017a
+], [
+    0x00a0, 0x02c7, 0x02d8, 0x0141, 0x00a4, 0x0104, 0x00a6, 0x00a7, 0x00a8, 0x00a9, 0x015e, 0x00ab, 0x00ac, 0x00ad, 0x00ae, 0x017b,
+    0x00b0, 0x00b1, 0x02db, 0x0142, 0x00b4, 0x00b5, 0x00b6, 0x00b7, 0x00b8, 0x0105, 0x015f, 0x00bb, 0x013d, 0x02dd, 0x013e, 0x017c
+], LATIN2_LETTERS);
+
+const ISO_8859_2: HighHalf = concat(C1_CONTROLS, [
+    0x00a0, 0x0104, 0x02d8, 0x0141, 0x00a4, 0x013d, 0x015a, 0x00a7, 0x00a8, 0x0160, 0x015e, 0x0164, 0x0179, 0x00ad, 0x017d, 0x017b,
+    0x00b0, 0x0105, 0x02db, 0x0142, 0x00b4, 0x013e, 0x015b, 0x02c7, 0x00b8, 0x0161, 0x015f, 0x0165, 0x017a, 0x02dd, 0x017e, 0x017c
+], LATIN2_LETTERS);
+
+const fn concat(controls: [u16; 32], symbols: [u16; 32], letters: [u16; 64]) -> HighHalf {
+    let mut table = [0; 128];
+    let mut index = 0;
+    while index < 32 {
+        table[index] = controls[index];
+        table[index + 32] = symbols[index];
+        table[index + 64] = letters[index];
+        table[index + 96] = letters[index + 32];
+        index += 1;
+    }
+
+    return table;
+}
+
+impl Encoding {
+    /// Looks up an encoding by one of its labels, e.g. `latin2` or the
+    /// `charset` of a `Content-Type` header. Labels are case-insensitive.
+    pub fn for_label(label: &str) -> Option<Encoding> {
+        let label = label.trim_matches(|char: char| char.is_ascii_whitespace()).to_ascii_lowercase();
+        let encoding = match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8" | "x-unicode20utf8" => Encoding::Utf8,
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16" | "utf-16le" => Encoding::Utf16Le,
+            "unicodefffe" | "utf-16be" => Encoding::Utf16Be,
+            "cp1250" | "windows-1250" | "x-cp1250" => Encoding::Windows1250,
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819" | "iso-8859-1" | "iso-ir-100"
+            | "iso8859-1" | "iso88591" | "iso_8859-1" | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii"
+            | "windows-1252" | "x-cp1252" => Encoding::Windows1252,
+            "csisolatin2" | "iso-8859-2" | "iso-ir-101" | "iso8859-2" | "iso88592" | "iso_8859-2" | "iso_8859-2:1987"
+            | "l2" | "latin2" => Encoding::Iso8859_2,
+            _ => return None
+        };
+
+        return Some(encoding);
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1250 => "windows-1250",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Iso8859_2 => "ISO-8859-2"
+        };
+    }
+
+    /// Decodes `bytes` into `output`, returns the number of bytes consumed.
+    /// A UTF-16 code unit or surrogate pair cut off at the end is left for the
+    /// next call unless `is_eof` is set, broken ones decode to U+FFFD. UTF-8
+    /// is validated by the parser itself and is not handled here.
+    pub(crate) fn decode(&self, bytes: &[u8], is_eof: bool, output: &mut String) -> usize {
+        let table = match self {
+            Encoding::Utf8 => unreachable!("UTF-8 is validated by the parser"),
+            Encoding::Utf16Le => return decode_utf16(bytes, is_eof, u16::from_le_bytes, output),
+            Encoding::Utf16Be => return decode_utf16(bytes, is_eof, u16::from_be_bytes, output),
+            Encoding::Windows1250 => &WINDOWS_1250,
+            Encoding::Windows1252 => &WINDOWS_1252,
+            Encoding::Iso8859_2 => &ISO_8859_2
+        };
+
+        output.reserve(bytes.len());
+        for byte in bytes {
+            let char = match byte {
+                0..=0x7f => char::from(*byte),
+                _ => char::from_u32(u32::from(table[usize::from(byte - 0x80)])).unwrap()
+            };
+
+            output.push(char);
+        }
+
+        return bytes.len();
+    }
+}
+
+fn decode_utf16(bytes: &[u8], is_eof: bool, unit: fn([u8; 2]) -> u16, output: &mut String) -> usize {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]])).collect();
+    let mut length = units.len();
+    if !is_eof && units.last().is_some_and(|last| (0xd800..0xdc00).contains(last)) {
+        // The trailing half of the surrogate pair has not been read yet.
+        length -= 1;
+    }
+
+    output.extend(char::decode_utf16(units[0..length].iter().copied()).map(|char| char.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    if is_eof && bytes.len() % 2 == 1 {
+        output.push(char::REPLACEMENT_CHARACTER);
+
+        return bytes.len();
+    }
+
+    return length * 2;
+}
+
+/// Determines the encoding of a source starting with `bytes`: a byte order
+/// mark wins over `transport_hint`, which wins over a `<meta>` declaration
+/// within the first `PRESCAN_LENGTH` bytes. Returns the encoding, the
+/// confidence in it and the length of the byte order mark.
+pub(crate) fn sniff(bytes: &[u8], transport_hint: Option<Encoding>) -> (Encoding, Confidence, usize) {
+    if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+        return (Encoding::Utf8, Confidence::Certain, 3);
+    }
+
+    if bytes.starts_with(&[0xfe, 0xff]) {
+        return (Encoding::Utf16Be, Confidence::Certain, 2);
+    }
+
+    if bytes.starts_with(&[0xff, 0xfe]) {
+        return (Encoding::Utf16Le, Confidence::Certain, 2);
+    }
+
+    if let Some(encoding) = transport_hint {
+        return (encoding, Confidence::Certain, 0);
+    }
+
+    let encoding = prescan(&bytes[0..bytes.len().min(PRESCAN_LENGTH)]).unwrap_or_default();
+
+    return (encoding, Confidence::Tentative, 0);
+}
+
+/// The "prescan a byte stream to determine its encoding" algorithm of the
+/// HTML standard.
+fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    let mut position = 0;
+    while position < bytes.len() {
+        let rest = &bytes[position..];
+        if rest.starts_with(b"<!--") {
+            position += find(&rest[2..], b"-->").map_or(rest.len(), |end| end + 5);
+        } else if starts_with_ignore_case(rest, b"<meta") && rest.get(5).is_some_and(|byte| is_whitespace(*byte) || *byte == b'/') {
+            position += 5;
+            if let Some(encoding) = prescan_meta(bytes, &mut position) {
+                return Some(encoding);
+            }
+        } else if rest[0] == b'<' && (starts_with_letter(&rest[1..]) || (rest.get(1) == Some(&b'/') && starts_with_letter(&rest[2..]))) {
+            position += rest.iter().position(|byte| is_whitespace(*byte) || *byte == b'>').unwrap_or(rest.len());
+            while read_attribute(bytes, &mut position).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            position += rest.iter().position(|byte| *byte == b'>').map_or(rest.len(), |end| end + 1);
+        } else {
+            position += 1;
+        }
+    }
+
+    return None;
+}
+
+/// Reads the attributes of a `<meta>` tag, returns the encoding it declares
+/// through `charset` or `http-equiv="Content-Type"` with `content`.
+fn prescan_meta(bytes: &[u8], position: &mut usize) -> Option<Encoding> {
+    let mut seen: Vec<Vec<u8>> = vec![];
+    let mut got_pragma = false;
+    let mut need_pragma: Option<bool> = None;
+    let mut charset: Option<Encoding> = None;
+
+    while let Some((name, value)) = read_attribute(bytes, position) {
+        if seen.contains(&name) {
+            continue;
+        }
+
+        match name.as_slice() {
+            b"http-equiv" if value.eq_ignore_ascii_case(b"content-type") => got_pragma = true,
+            b"content" if charset.is_none() => {
+                if let Some(encoding) = charset_from_content(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+
+            b"charset" if charset.is_none() => {
+                charset = Encoding::for_label(&String::from_utf8_lossy(&value));
+                need_pragma = Some(false);
+            }
+
+            _ => {}
+        }
+
+        seen.push(name);
+    }
+
+    if need_pragma.is_none() || (need_pragma == Some(true) && !got_pragma) {
+        return None;
+    }
+
+    return match charset? {
+        Encoding::Utf16Le | Encoding::Utf16Be => Some(Encoding::Utf8),
+        encoding => Some(encoding)
+    };
+}
+
+/// The "get an attribute" algorithm of the prescan, names are lowercased.
+fn read_attribute(bytes: &[u8], position: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    let byte_at = |position: usize| bytes.get(position).copied();
+    while byte_at(*position).is_some_and(|byte| is_whitespace(byte) || byte == b'/') {
+        *position += 1;
+    }
+
+    if byte_at(*position).is_none_or(|byte| byte == b'>') {
+        return None;
+    }
+
+    let mut name = vec![];
+    let mut value = vec![];
+    loop {
+        match byte_at(*position)? {
+            b'=' if !name.is_empty() => break,
+            byte if is_whitespace(byte) => {
+                while byte_at(*position).is_some_and(is_whitespace) {
+                    *position += 1;
+                }
+
+                if byte_at(*position) != Some(b'=') {
+                    return Some((name, value));
+                }
+
+                break;
+            }
+
+            b'/' | b'>' => return Some((name, value)),
+            byte => name.push(byte.to_ascii_lowercase())
+        }
+
+        *position += 1;
+    }
+
+    // Skips the `=` and whitespace in front of the value.
+    *position += 1;
+    while byte_at(*position).is_some_and(is_whitespace) {
+        *position += 1;
+    }
+
+    match byte_at(*position)? {
+        quote @ (b'"' | b'\'') => {
+            *position += 1;
+            loop {
+                let byte = byte_at(*position)?;
+                *position += 1;
+                if byte == quote {
+                    return Some((name, value));
+                }
+
+                value.push(byte.to_ascii_lowercase());
+            }
+        }
+
+        b'>' => return Some((name, value)),
+        _ => {}
+    }
+
+    while let Some(byte) = byte_at(*position) {
+        if is_whitespace(byte) || byte == b'>' {
+            break;
+        }
+
+        value.push(byte.to_ascii_lowercase());
+        *position += 1;
+    }
+
+    return Some((name, value));
+}
+
+/// The "extracting character encodings from meta elements" algorithm, used
+/// for `content="text/html; charset=..."`.
+fn charset_from_content(content: &[u8]) -> Option<Encoding> {
+    let mut position = 0;
+    loop {
+        position += find_ignore_case(&content[position..], b"charset")? + 7;
+        while content.get(position).is_some_and(|byte| is_whitespace(*byte)) {
+            position += 1;
+        }
+
+        if content.get(position) == Some(&b'=') {
+            position += 1;
+            break;
+        }
+    }
+
+    while content.get(position).is_some_and(|byte| is_whitespace(*byte)) {
+        position += 1;
+    }
+
+    let rest = &content[position..];
+    let value = match rest.first()? {
+        quote @ (b'"' | b'\'') => &rest[1..1 + rest[1..].iter().position(|byte| byte == quote)?],
+        _ => &rest[0..rest.iter().position(|byte| is_whitespace(*byte) || *byte == b';').unwrap_or(rest.len())]
+    };
+
+    return Encoding::for_label(&String::from_utf8_lossy(value));
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    return matches!(byte, b'\t' | b'\n' | b'\x0c' | b'\r' | b' ');
+}
+
+fn starts_with_letter(bytes: &[u8]) -> bool {
+    return bytes.first().is_some_and(u8::is_ascii_alphabetic);
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    return bytes.len() >= prefix.len() && bytes[0..prefix.len()].eq_ignore_ascii_case(prefix);
+}
+
+fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    return bytes.windows(needle.len()).position(|window| window == needle);
+}
+
+fn find_ignore_case(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    return bytes.windows(needle.len()).position(|window| window.eq_ignore_ascii_case(needle));
+}
@@ -0,0 +1,72 @@
+#![allow(clippy::needless_return)]
+
+use std::{error, fmt, io};
+
+use crate::position::Position;
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// Reading from the source failed. The parser stops after this error.
+    Io { error: io::Error, position: Position },
+    /// The source contains a byte sequence that is not valid UTF-8.
+    InvalidUtf8 { position: Position },
+    /// The source ended inside of a tag, `position` points at its `<`.
+    UnexpectedEof { position: Position },
+    /// An attribute contains a character that is not allowed at its position.
+    MalformedAttribute { name: String, position: Position },
+    /// An attribute is defined more than once in the same tag.
+    DuplicateAttribute { name: String, position: Position }
+}
+
+impl ParseError {
+    pub fn position(&self) -> Position {
+        match self {
+            ParseError::Io { position, .. }
+            | ParseError::InvalidUtf8 { position }
+            | ParseError::UnexpectedEof { position }
+            | ParseError::MalformedAttribute { position, .. }
+            | ParseError::DuplicateAttribute { position, .. } => *position
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io { error, .. } => write!(formatter, "I/O error: {}", error)?,
+            ParseError::InvalidUtf8 { .. } => write!(formatter, "invalid UTF-8 sequence")?,
+            ParseError::UnexpectedEof { .. } => write!(formatter, "unexpected end of file in tag")?,
+            ParseError::MalformedAttribute { name, .. } => write!(formatter, "malformed attribute `{}`", name)?,
+            ParseError::DuplicateAttribute { name, .. } => write!(formatter, "duplicate attribute `{}`", name)?
+        }
+
+        let position = self.position();
+
+        return write!(formatter, " at line {}, column {} (byte {})", position.line, position.column, position.offset);
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::Io { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
+
+/// A CSS selector could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorError {
+    pub message: &'static str,
+    /// Byte offset into the selector text.
+    pub offset: usize
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(formatter, "{} at byte {} of selector", self.message, self.offset);
+    }
+}
+
+impl error::Error for SelectorError {}
@@ -0,0 +1,107 @@
+#![allow(clippy::needless_return)]
+
+use std::io::Read;
+use std::iter::FusedIterator;
+
+use crate::element::HtmlElement;
+use crate::error::{ParseError, SelectorError};
+use crate::position::Span;
+use crate::selector::Selector;
+use crate::{HtmlEvent, HtmlParser};
+
+/// Passes on only the events inside elements matching a selector, including
+/// the opening and closing events of the matching elements themselves. Errors
+/// and the document end are always passed on. A matched element closed by the
+/// end tag of an ancestor or by the end of the document gets a closing event
+/// with an empty span.
+///
+/// Elements are matched against the open elements of the parser, so memory
+/// use does not grow with the document. Selectors are limited to type,
+/// universal, class, id and attribute selectors joined by descendant or child
+/// combinators.
+pub struct HtmlFilter<'a, R> {
+    parser: HtmlParser<'a, R>,
+    selector: Selector,
+    /// Matched element whose events are passed on.
+    matched: Option<HtmlElement<'a>>
+}
+
+impl<'a, R:Read> HtmlFilter<'a, R> {
+    pub fn new(parser: HtmlParser<'a, R>, selectors: &str) -> Result<HtmlFilter<'a, R>, SelectorError> {
+        let selector = Selector::parse(selectors)?;
+        if !selector.is_streamable() {
+            return Err(SelectorError { message: "selector cannot be matched while streaming", offset: 0 });
+        }
+
+        return Ok(HtmlFilter { parser, selector, matched: None });
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<HtmlEvent<'a>, ParseError> {
+        loop {
+            let event = self.parser.next()?;
+            if let Some(matched) = &self.matched {
+                // Closing tags that do not close an open element are deeper
+                // than the element they appear in. The closing tag of an
+                // ancestor and the document end close the matched element
+                // without being part of the match.
+                let is_matched_end = matches!(&event, HtmlEvent::HtmlElementClosed { closed_element } if closed_element.depth == matched.depth);
+                let implied_end = match &event {
+                    HtmlEvent::HtmlElementClosed { closed_element } if closed_element.depth < matched.depth => Some(closed_element.span.start),
+                    HtmlEvent::HtmlDocumentEnd => Some(self.parser.position()),
+                    _ => None
+                };
+
+                if let Some(position) = implied_end {
+                    let matched = self.matched.take().unwrap();
+                    let closed_element = HtmlElement { span: Span::new(position, position), ..matched };
+
+                    return Ok(HtmlEvent::HtmlElementClosed { closed_element });
+                }
+
+                if is_matched_end {
+                    self.matched = None;
+                }
+
+                return Ok(event);
+            }
+
+            match &event {
+                HtmlEvent::HtmlElementOpened { opened_element } => {
+                    let ancestors = &self.parser.ancestors()[0..opened_element.depth];
+                    if !self.selector.matches_path(opened_element, ancestors) {
+                        continue;
+                    }
+
+                    // Void elements have no content and no closing event.
+                    if !opened_element.name.is_self_closing_element() {
+                        self.matched = Some(opened_element.clone());
+                    }
+
+                    return Ok(event);
+                }
+
+                HtmlEvent::HtmlDocumentEnd => {
+                    return Ok(event);
+                }
+
+                _ => continue
+            }
+        }
+    }
+}
+
+impl<'a, R:Read> Iterator for HtmlFilter<'a, R> {
+    type Item = Result<HtmlEvent<'a>, ParseError>;
+
+    /// Yields the filtered events until the end of the document,
+    /// `HtmlEvent::HtmlDocumentEnd` itself is not yielded.
+    fn next(&mut self) -> Option<Self::Item> {
+        match HtmlFilter::next(self) {
+            Ok(HtmlEvent::HtmlDocumentEnd) => None,
+            event => Some(event)
+        }
+    }
+}
+
+impl<'a, R:Read> FusedIterator for HtmlFilter<'a, R> {}
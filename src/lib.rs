@@ -1,163 +1,641 @@
-use std::io::Read;
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use std::borrow::Cow;
+use std::io::{ErrorKind, Read};
+use std::iter::FusedIterator;
 use std::collections::{HashMap, VecDeque};
-use crate::element::{HtmlElement, HtmlElementName};
+use crate::doctype::{Doctype, QuirksMode};
+use crate::element::{HtmlElement, HtmlElementName, Namespace};
+use crate::encoding::{Confidence, Encoding, InvalidUtf8, PRESCAN_LENGTH};
+use crate::error::ParseError;
+use crate::position::{AttributeSpan, Position, Span};
+use crate::stack::ElementStack;
+use crate::tree::TreeBuilder;
 
+pub mod doctype;
+pub mod dom;
 pub mod element;
+pub mod encoding;
+pub mod entities;
+pub mod error;
+pub mod filter;
+pub mod position;
+pub mod rewriter;
+pub mod selector;
+mod stack;
+mod tree;
+pub mod writer;
+
+const CHUNK_SIZE: usize = 64 * 1024;
 
 #[derive(Debug)]
-pub enum HtmlEvent {
-    HtmlElementOpened { opened_element: element::HtmlElement },
-    HtmlElementClosed { closed_element: element::HtmlElement },
-    TextContent(String),
+pub enum HtmlEvent<'a> {
+    HtmlElementOpened { opened_element: element::HtmlElement<'a> },
+    HtmlElementClosed { closed_element: element::HtmlElement<'a> },
+    /// `depth` is the number of elements the text is nested in.
+    TextContent { content: Cow<'a, str>, span: Span, depth: usize },
+    Comment { content: Cow<'a, str>, span: Span, depth: usize },
+    /// `<!DOCTYPE name PUBLIC "public_id" "system_id">`, `force_quirks` is set
+    /// for declarations missing a name or broken off, see `doctype::quirks_mode`.
+    Doctype {
+        name: Option<Cow<'a, str>>,
+        public_id: Option<Cow<'a, str>>,
+        system_id: Option<Cow<'a, str>>,
+        force_quirks: bool,
+        span: Span
+    },
     HtmlDocumentEnd,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AttributeState {
+    BeforeName,
+    Name,
+    AfterName,
+    BeforeValue,
+    DoubleQuotedValue,
+    SingleQuotedValue,
+    AfterQuotedValue,
+    UnquotedValue,
+    SelfClosing
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextState {
+    Data,
+    /// Text with character references, as in `textarea` and `title`.
+    RcData,
+    /// Text without character references, as in `style` or `xmp`.
+    RawText,
+    ScriptData,
+    /// Everything up to the end of the source is text.
+    PlainText
+}
+
+/// A `<` or `</` seen in data, whether it starts a tag depends on the
+/// character behind it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TagOpen {
+    LessThan,
+    Solidus
+}
+
+/// Escape states of script data, a `</script>` inside of `<!-- <script>`
+/// does not end the script.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScriptState {
+    Unescaped,
+    LessThan,
+    EscapeStart,
+    EscapeStartDash,
+    Escaped,
+    EscapedDash,
+    EscapedDashDash,
+    EscapedLessThan,
+    DoubleEscapeStart,
+    DoubleEscaped,
+    DoubleEscapedDash,
+    DoubleEscapedDashDash,
+    DoubleEscapedLessThan,
+    DoubleEscapeEnd
+}
+
 #[derive(Debug)]
-pub struct HtmlParserContext {
+pub struct HtmlParserContext<'a> {
     current_element: Option<HtmlElementName>,
-    elements: Vec<element::HtmlElement>,
+    elements: ElementStack<element::HtmlElement<'a>>,
     inside_brackets: bool,
+    tag_open: Option<TagOpen>,
+    inside_comment: bool,
+    inside_bogus_comment: bool,
+    inside_doctype: bool,
+    /// Tokenizer state for the content of the last opened element.
+    text_state: TextState,
+    /// Element whose end tag ends `text_state`.
+    raw_text_element: Option<HtmlElementName>,
+    /// Offset in `text_content` of a `<` that may start that end tag.
+    end_tag_start: Option<usize>,
+    script_state: ScriptState,
     is_closing_element: bool,
-    defined_attributes: HashMap<String, String>,
+    attribute_state: AttributeState,
+    attribute_name: String,
+    attribute_value: String,
+    attribute_span: AttributeSpan,
+    defined_attributes: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    defined_attribute_spans: HashMap<Cow<'a, str>, AttributeSpan>,
     text_content: String,
     skip_content_fillup: bool,
     buffer_vec: Vec<u8>,
-    events: VecDeque<HtmlEvent>
+    read_buffer: Vec<u8>,
+    /// Validated text read from the source, consumed up to `text_consumed`.
+    text_buffer: String,
+    text_consumed: usize,
+    /// Set once the encoding of `buffer_vec` has been sniffed.
+    encoding: Option<(Encoding, Confidence)>,
+    /// Read error held back until the bytes read before it are tokenized.
+    read_error: Option<std::io::Error>,
+    source_ended: bool,
+    position: Position,
+    tag_start: Position,
+    text_span: Option<Span>,
+    is_finished: bool,
+    events: VecDeque<Result<HtmlEvent<'a>, ParseError>>,
+    /// Decided by the first event that is not a comment.
+    quirks_mode: Option<QuirksMode>,
+    /// Present when `HtmlParserOptions::tree_construction` is set.
+    tree: Option<TreeBuilder<'a>>,
+    /// In tree construction mode, the elements left open by the events
+    /// returned so far. The tree builder's stack runs ahead of them while
+    /// events are held back for foster parenting.
+    returned_elements: Vec<HtmlElement<'a>>
+}
+
+/// Reporting of whitespace in text content. The content of raw text elements,
+/// such as `script` or `textarea`, is always kept as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WhitespaceMode {
+    /// Keep the text as it is in the source, text of only whitespace included.
+    #[default]
+    Preserve,
+    /// Replace every run of whitespace with a single space.
+    Collapse,
+    /// Collapse whitespace and strip it from both ends of the text, text of
+    /// only whitespace is dropped.
+    Trim
+}
+
+#[derive(Debug, Clone)]
+pub struct HtmlParserOptions {
+    /// Decode character references (`&amp;`, `&#x2014;`, ...) in text content
+    /// and attribute values. Disable to keep the source text for round-tripping.
+    pub decode_entities: bool,
+    /// Report recoverable markup errors, such as malformed attributes or a tag
+    /// left open at the end of the source, as `Err` from `HtmlParser::next`.
+    /// Parsing can continue after such an error.
+    pub strict: bool,
+    /// Run the tokens through the HTML tree construction stage: implied end
+    /// tags, implied `html`, `head`, `body` and table elements, the adoption
+    /// agency algorithm and foster parenting. Every opened element is then
+    /// closed by exactly one `HtmlElementClosed` event, synthetic ones carry an
+    /// empty span, and stray end tags are dropped.
+    ///
+    /// Emitted events are never taken back, which makes two cases differ from
+    /// the spec. When the adoption agency algorithm finds a block inside of a
+    /// misnested formatting element, the block is closed with the formatting
+    /// element and reopened behind it: `<b>1<p>2</b>3</p>` gives
+    /// `<b>1<p>2</p></b><p>3</p>` instead of `<b>1</b><p><b>2</b>3</p>`.
+    /// A `frameset` start tag is ignored once the body has been opened.
+    pub tree_construction: bool,
+    /// Encoding given by the transport layer, such as the `charset` of a
+    /// `Content-Type` header. It is only overridden by a byte order mark.
+    /// Without it the encoding is sniffed from `<meta>` declarations in the
+    /// first kilobyte, falling back to UTF-8.
+    pub transport_encoding: Option<Encoding>,
+    /// What to do with byte sequences that are not valid UTF-8 in a source
+    /// decoded as UTF-8.
+    pub invalid_utf8: InvalidUtf8,
+    /// How whitespace in text content is reported.
+    pub whitespace: WhitespaceMode
 }
 
-pub struct HtmlParser<R> {
+impl Default for HtmlParserOptions {
+    fn default() -> HtmlParserOptions {
+        return HtmlParserOptions {
+            decode_entities: true,
+            strict: false,
+            tree_construction: false,
+            transport_encoding: None,
+            invalid_utf8: InvalidUtf8::Replace,
+            whitespace: WhitespaceMode::Preserve
+        };
+    }
+}
+
+pub struct HtmlParser<'a, R> {
     source: R,
-    context: HtmlParserContext
+    /// The whole document when it is parsed from memory, events borrow from it.
+    input: Option<&'a str>,
+    options: HtmlParserOptions,
+    context: HtmlParserContext<'a>
+}
+
+impl<'a> HtmlParser<'a, &'a [u8]> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &'a str) -> HtmlParser<'a, &'a [u8]> {
+        return HtmlParser::from_str_with_options(source, HtmlParserOptions::default());
+    }
+
+    pub fn from_str_with_options(source: &'a str, options: HtmlParserOptions) -> HtmlParser<'a, &'a [u8]> {
+        // A leading byte order mark is skipped like `from_bytes` does, offsets
+        // still count from the start of `source`.
+        let byte_order_mark = if source.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+        let mut parser = HtmlParser::with_options(&source.as_bytes()[byte_order_mark..], options);
+        parser.input = Some(source);
+        parser.context.encoding = Some((Encoding::Utf8, Confidence::Irrelevant));
+        parser.context.position.offset = byte_order_mark;
+
+        return parser;
+    }
+
+    pub fn from_bytes(source: &'a [u8]) -> HtmlParser<'a, &'a [u8]> {
+        return HtmlParser::from_bytes_with_options(source, HtmlParserOptions::default());
+    }
+
+    /// Text, comments and attributes are borrowed from `source` unless decoding
+    /// changes them. Sources that are not valid UTF-8 or sniffed to be in
+    /// another encoding are read like any other reader and yield owned data.
+    pub fn from_bytes_with_options(source: &'a [u8], options: HtmlParserOptions) -> HtmlParser<'a, &'a [u8]> {
+        let mut parser = HtmlParser::with_options(source, options);
+        let byte_order_mark = parser.sniff_encoding(source);
+        parser.source = &source[byte_order_mark..];
+        if parser.encoding() == Encoding::Utf8 {
+            parser.input = std::str::from_utf8(source).ok();
+        }
+
+        return parser;
+    }
 }
 
-impl<R:Read> HtmlParser<R> {
-    pub fn new (source: R) -> HtmlParser<R> {
+impl<'a, R:Read> HtmlParser<'a, R> {
+    pub fn new (source: R) -> HtmlParser<'a, R> {
+        return HtmlParser::with_options(source, HtmlParserOptions::default());
+    }
+
+    pub fn with_options(source: R, options: HtmlParserOptions) -> HtmlParser<'a, R> {
         let context = HtmlParserContext {
             current_element: None,
-            elements: vec![],
+            elements: ElementStack::new(),
             inside_brackets: false,
+            tag_open: None,
+            inside_comment: false,
+            inside_bogus_comment: false,
+            inside_doctype: false,
+            text_state: TextState::Data,
+            raw_text_element: None,
+            end_tag_start: None,
+            script_state: ScriptState::Unescaped,
             is_closing_element: false,
+            attribute_state: AttributeState::BeforeName,
+            attribute_name: String::new(),
+            attribute_value: String::new(),
+            attribute_span: AttributeSpan::default(),
             defined_attributes: HashMap::new(),
+            defined_attribute_spans: HashMap::new(),
             text_content: String::new(),
             skip_content_fillup: false,
             buffer_vec: vec![],
-            events: VecDeque::new()
+            read_buffer: vec![],
+            text_buffer: String::new(),
+            text_consumed: 0,
+            encoding: None,
+            read_error: None,
+            source_ended: false,
+            position: Position::new(),
+            tag_start: Position::new(),
+            text_span: None,
+            is_finished: false,
+            events: VecDeque::new(),
+            quirks_mode: None,
+            tree: options.tree_construction.then(TreeBuilder::new),
+            returned_elements: vec![]
         };
 
-        return HtmlParser { source, context };
+        return HtmlParser { source, input: None, options, context };
     }
 
     fn fill_element_from_text_content(&mut self) -> Option<HtmlElementName> {
         if self.context.inside_brackets && self.context.current_element.is_none() {
-            let element_name = HtmlElementName::from_str(self.context.text_content.as_str());
+            let text = self.context.text_content.as_str();
+            if text.len() >= 8 && text.is_char_boundary(8) && text[0..8].eq_ignore_ascii_case("!doctype") {
+                // A name glued to the keyword (`<!DOCTYPEhtml>`) belongs to
+                // the declaration text.
+                self.context.text_content.drain(0..8);
+                self.context.current_element = Some(HtmlElementName::Doctype);
+                self.context.inside_doctype = true;
+
+                return Some(HtmlElementName::Doctype);
+            }
+
+            let element_name = HtmlElementName::from_str(text);
             
-            if element_name.is_ok() {
-                self.context.current_element = Some(element_name.unwrap());
-                self.context.text_content = String::new();
-                let name = self.context.current_element.as_ref().unwrap();
-                let current_name = HtmlElementName::from_str(name.to_str()).unwrap();
+            if let Ok(element_name) = element_name {
+                self.context.current_element = Some(element_name.clone());
+                self.context.text_content.clear();
 
-                return Some(current_name);
+                return Some(element_name);
             }
             
             return None;
         }
 
-        if self.context.current_element.is_none() {
-            return None;
+        return self.context.current_element.clone();
+    }
+
+    fn finish_attribute(&mut self) -> () {
+        let span = std::mem::take(&mut self.context.attribute_span);
+        if self.context.attribute_name.is_empty() {
+            self.context.attribute_value.clear();
+            return;
         }
 
-        let name = self.context.current_element.as_ref().unwrap();
-        let current_name = HtmlElementName::from_str(name.to_str()).unwrap();
+        let name = self.borrow_input(&self.context.attribute_name, span.name.end.offset);
+        let mut value = match span.value {
+            Some(value_span) => self.borrow_input(&self.context.attribute_value, value_span.end.offset),
+            None => Cow::Borrowed("")
+        };
 
-        return Some(current_name);
-    }
+        self.context.attribute_name.clear();
+        self.context.attribute_value.clear();
+        if self.options.decode_entities {
+            value = decode_entities(value, true);
+        }
+
+        if self.context.defined_attributes.contains_key(&name) {
+            let name = name.into_owned();
+            let position = self.context.position;
+            self.report_error(ParseError::DuplicateAttribute { name, position });
 
-    fn fill_attritube(&mut self) -> () {
-        if !self.context.inside_brackets 
-            || self.context.current_element.is_none() 
-            || self.context.text_content.is_empty() 
-        {
             return;
         }
 
-        let characters_count: Vec<&str> = self.context.text_content.matches('"').collect();
-        if (self.context.text_content.contains('"') && characters_count.len() == 2) 
-            || characters_count.len() == 0
-        {
-            let split: Vec<&str> = self.context.text_content.split('=').collect();
-            let attr_name = split[0].trim().to_string();
-            let mut value = split[1..].join("=");
-            if value.starts_with('"') {
-                value.remove(0);
+        self.context.defined_attribute_spans.insert(name.clone(), span);
+        self.context.defined_attributes.insert(name, value);
+    }
+
+    fn report_malformed_attribute(&mut self) -> () {
+        let name = self.context.attribute_name.clone();
+        let position = self.context.position;
+        self.report_error(ParseError::MalformedAttribute { name, position });
+    }
+
+    fn handle_attribute_character(&mut self, sign: &str) -> Option<HtmlEvent<'a>> {
+        let char = sign.chars().next()?;
+        let state = self.context.attribute_state;
+        if char == '>' && !matches!(state, AttributeState::DoubleQuotedValue | AttributeState::SingleQuotedValue) {
+            self.finish_attribute();
+
+            return self.handle_closing_bracket();
+        }
+
+        match state {
+            AttributeState::BeforeName => {
+                if char.is_whitespace() {
+                    return None;
+                }
+
+                if char == '/' {
+                    self.context.attribute_state = AttributeState::SelfClosing;
+
+                    return None;
+                }
+
+                self.push_attribute_name_character(char);
+                self.context.attribute_state = AttributeState::Name;
+                if matches!(char, '"' | '\'' | '<' | '=') {
+                    self.report_malformed_attribute();
+                }
+            }
+
+            AttributeState::Name => {
+                if matches!(char, '"' | '\'' | '<') {
+                    self.report_malformed_attribute();
+                }
+
+                if char.is_whitespace() {
+                    self.context.attribute_state = AttributeState::AfterName;
+                } else if char == '/' {
+                    self.finish_attribute();
+                    self.context.attribute_state = AttributeState::SelfClosing;
+                } else if char == '=' {
+                    self.context.attribute_state = AttributeState::BeforeValue;
+                } else {
+                    self.push_attribute_name_character(char);
+                }
+            }
+
+            AttributeState::AfterName => {
+                if char.is_whitespace() {
+                    return None;
+                }
+
+                if char == '=' {
+                    self.context.attribute_state = AttributeState::BeforeValue;
+                } else if char == '/' {
+                    self.finish_attribute();
+                    self.context.attribute_state = AttributeState::SelfClosing;
+                } else {
+                    self.finish_attribute();
+                    self.push_attribute_name_character(char);
+                    self.context.attribute_state = AttributeState::Name;
+                    if matches!(char, '"' | '\'' | '<') {
+                        self.report_malformed_attribute();
+                    }
+                }
+            }
+
+            AttributeState::BeforeValue => {
+                if char.is_whitespace() {
+                    return None;
+                }
+
+                if char == '"' || char == '\'' {
+                    let start = self.position_after(sign);
+                    self.context.attribute_span.value = Some(Span::new(start, start));
+                    self.context.attribute_state = if char == '"' {
+                        AttributeState::DoubleQuotedValue
+                    } else {
+                        AttributeState::SingleQuotedValue
+                    };
+                } else {
+                    return self.handle_unquoted_value_character(char);
+                }
+            }
+
+            AttributeState::DoubleQuotedValue | AttributeState::SingleQuotedValue => {
+                let quote = if state == AttributeState::DoubleQuotedValue { '"' } else { '\'' };
+                if char == quote {
+                    self.context.attribute_state = AttributeState::AfterQuotedValue;
+                } else {
+                    self.push_attribute_value_character(char);
+                }
+            }
+
+            AttributeState::AfterQuotedValue => {
+                if !char.is_whitespace() && char != '/' {
+                    self.report_malformed_attribute();
+                }
+
+                self.finish_attribute();
+                self.context.attribute_state = AttributeState::BeforeName;
+
+                return self.handle_attribute_character(sign);
             }
 
-            if value.ends_with('"') {
-                value.remove(value.len() - 1);
+            AttributeState::UnquotedValue => {
+                if char.is_whitespace() {
+                    self.finish_attribute();
+                    self.context.attribute_state = AttributeState::BeforeName;
+                } else {
+                    return self.handle_unquoted_value_character(char);
+                }
             }
 
-            self.context.defined_attributes.insert(attr_name, value);
-            self.context.text_content = String::new();
+            AttributeState::SelfClosing => {
+                self.context.attribute_state = AttributeState::BeforeName;
+
+                return self.handle_attribute_character(sign);
+            }
         }
 
-        return;
+        return None;
     }
 
-    fn handle_whitespace(&mut self) -> Option<HtmlEvent> {
-        if self.context.text_content.is_empty() {
-            return None;
+    fn handle_unquoted_value_character(&mut self, char: char) -> Option<HtmlEvent<'a>> {
+        if matches!(char, '"' | '\'' | '<' | '=' | '`') {
+            self.report_malformed_attribute();
         }
 
-        let element_filled = self.fill_element_from_text_content();
-        if element_filled.is_none() {
-            return None;
-        }
+        self.push_attribute_value_character(char);
+        self.context.attribute_state = AttributeState::UnquotedValue;
 
-        self.fill_attritube();
-        
         return None;
     }
 
-    fn push_element(&mut self, element: HtmlElement) -> () {
-        if element.name.is_self_closing_element() {
-            return;
+    fn push_attribute_name_character(&mut self, char: char) -> () {
+        if self.context.attribute_name.is_empty() {
+            self.context.attribute_span.name.start = self.context.position;
         }
 
+        self.context.attribute_name.push(char.to_ascii_lowercase());
+        self.context.attribute_span.name.end = self.position_after(char.encode_utf8(&mut [0; 4]));
+    }
+
+    fn push_attribute_value_character(&mut self, char: char) -> () {
+        let start = self.context.position;
+        let end = self.position_after(char.encode_utf8(&mut [0; 4]));
+        let value_span = self.context.attribute_span.value.get_or_insert(Span::new(start, start));
+        value_span.end = end;
+        self.context.attribute_value.push(char);
+    }
+
+    fn position_after(&self, sign: &str) -> Position {
+        let mut position = self.context.position;
+        position.advance(sign);
+
+        return position;
+    }
+
+    fn handle_whitespace(&mut self) -> Option<HtmlEvent<'a>> {
+        if self.context.text_content.is_empty() {
+            return None;
+        }
+
+        self.fill_element_from_text_content()?;
+        self.context.skip_content_fillup = self.context.inside_brackets;
+
+        return None;
+    }
+
+    fn push_element(&mut self, element: HtmlElement<'a>) -> () {
         self.context.elements.push(element);
     }
 
-    fn pop_element(&mut self, element: HtmlElement) -> () {
-        let last_element_opt = self.context.elements.last();
-        if last_element_opt.is_none() {
-            return ();
+    fn pop_element(&mut self, element: &HtmlElement<'a>) -> () {
+        self.context.elements.pop(&element.name);
+    }
+
+    /// Namespace of an element opened inside of the current element, HTML
+    /// integration points such as `foreignObject` or `mi` switch back to HTML.
+    fn namespace_of(&self, element: &HtmlElement) -> Namespace {
+        if element.name.is_element(HtmlElementName::Svg) {
+            return Namespace::Svg;
         }
 
-        if last_element_opt.unwrap().name.is_element(element.name) {
-            self.context.elements.pop();
+        if element.name.is_element(HtmlElementName::Math) {
+            return Namespace::MathMl;
         }
+
+        let Some(parent) = self.open_elements().last() else {
+            return Namespace::Html;
+        };
+
+        return match (parent.namespace, parent.name.to_str()) {
+            (Namespace::Svg, "foreignObject" | "desc" | "title") => Namespace::Html,
+            (Namespace::MathMl, "mi" | "mo" | "mn" | "ms" | "mtext") if !matches!(element.name.to_str(), "mglyph" | "malignmark") => Namespace::Html,
+            (namespace, _) => namespace
+        };
     }
 
-    fn handle_closing_bracket(&mut self) -> Option<HtmlEvent> {
+    fn handle_closing_bracket(&mut self) -> Option<HtmlEvent<'a>> {
         let element_filled = self.fill_element_from_text_content();
+        if self.context.inside_doctype {
+            return Some(self.take_doctype(true));
+        }
+
         if self.context.inside_brackets && element_filled.is_none() {
+            // `<!` not followed by `--` or `DOCTYPE` is a bogus comment.
+            let mut event: Option<HtmlEvent<'a>> = None;
+            if let Some(content) = self.context.text_content.strip_prefix('!') {
+                let span = Span::new(self.context.tag_start, self.position_after(">"));
+                let content = self.borrow_input(content, span.end.offset - 1);
+                event = Some(HtmlEvent::Comment { content, span, depth: self.depth() });
+                self.context.text_content.clear();
+            }
+
             self.context.inside_brackets = false;
             self.context.is_closing_element = false;
             self.context.defined_attributes = HashMap::new();
+            self.context.defined_attribute_spans = HashMap::new();
 
-            return None;
+            return event;
         }
 
-        self.fill_attritube();
-
-        let element = HtmlElement {
+        let mut element = HtmlElement {
             name: self.context.current_element.clone().unwrap(),
-            attributes: self.context.defined_attributes.clone()
+            namespace: Namespace::Html,
+            attributes: std::mem::take(&mut self.context.defined_attributes),
+            self_closing: self.context.attribute_state == AttributeState::SelfClosing,
+            span: Span::new(self.context.tag_start, self.position_after(">")),
+            attribute_spans: std::mem::take(&mut self.context.defined_attribute_spans),
+            depth: self.depth()
         };
 
-        let event: HtmlEvent;
         if self.context.is_closing_element {
-            event = HtmlEvent::HtmlElementClosed { closed_element: element.clone() };
-            self.pop_element(element);
+            // End tags take the name and namespace of the element they close.
+            let name = element.name.to_str();
+            if let Some(open) = self.open_elements().iter().rev().find(|open| open.name.to_str().eq_ignore_ascii_case(name)) {
+                element.name = open.name.clone();
+                element.namespace = open.namespace;
+            }
+        } else {
+            element.namespace = self.namespace_of(&element);
+            element.adjust_foreign_names();
+        }
+
+        if !self.context.is_closing_element && element.namespace == Namespace::Html {
+            self.context.text_state = text_state_of(&element.name);
+            if self.context.text_state != TextState::Data {
+                self.context.raw_text_element = Some(element.name.clone());
+                self.context.script_state = ScriptState::Unescaped;
+            }
+        }
+
+        let event: HtmlEvent<'a>;
+        if self.context.tree.is_some() {
+            // The tree builder keeps its own stack of open elements.
+            event = match self.context.is_closing_element {
+                true => HtmlEvent::HtmlElementClosed { closed_element: element },
+                false => HtmlEvent::HtmlElementOpened { opened_element: element }
+            };
+        } else if self.context.is_closing_element {
+            self.pop_element(&element);
+            element.depth = self.depth();
+            event = HtmlEvent::HtmlElementClosed { closed_element: element };
+        } else if element.self_closing && element.namespace != Namespace::Html {
+            // Self-closing foreign elements are closed right away, the closing
+            // event is queued behind the opening one by the caller.
+            self.context.events.push_back(Ok(HtmlEvent::HtmlElementOpened { opened_element: element.clone() }));
+            event = HtmlEvent::HtmlElementClosed { closed_element: element };
         } else {
             event = HtmlEvent::HtmlElementOpened { opened_element: element.clone() };
             self.push_element(element);
@@ -166,148 +644,945 @@ impl<R:Read> HtmlParser<R> {
         self.context.inside_brackets = false;
         self.context.is_closing_element = false;
         self.context.current_element = None;
-        self.context.defined_attributes = HashMap::new();
-        self.context.text_content = String::new();
+        self.context.attribute_state = AttributeState::BeforeName;
+        self.context.text_content.clear();
         self.context.skip_content_fillup = true;
         
         return Some(event);
     }
 
-    fn handle_closing_element(&mut self) -> Option<HtmlEvent> {
-        let last_element = self.context.elements.last();
-        let mut event: Option<HtmlEvent> = None;
-        if last_element.is_some() 
-            && (
-                last_element.unwrap().name.is_element(HtmlElementName::Script)
-                || last_element.unwrap().name.is_element(HtmlElementName::Style)
-            ) && self.context.text_content.trim().ends_with("<")
-        {
-            let mut content = self.context.text_content.trim().to_string();
-            content = content.as_str()[0..content.len() - 1].to_string();
-            if !content.is_empty() {
-                event = Some(HtmlEvent::TextContent(content));
-            }
-
-            self.context.skip_content_fillup = true;
-            self.context.text_content = String::new();
-            self.context.inside_brackets = true;
-            self.context.is_closing_element = true;
-        } else if self.context.inside_brackets 
-            && self.context.text_content.clone().trim().is_empty() 
+    fn handle_closing_element(&mut self) -> () {
+        if self.context.inside_brackets 
+            && self.context.text_content.trim().is_empty() 
         {
             self.context.is_closing_element = self.context.current_element.is_none();
             self.context.skip_content_fillup = true;
+        } else if self.context.inside_brackets && self.fill_element_from_text_content().is_some() {
+            self.context.attribute_state = AttributeState::SelfClosing;
+            self.context.skip_content_fillup = true;
         }
-
-        return event;
     }
 
-    fn handle_opening_bracket(&mut self) -> Option<HtmlEvent> {
-        if self.context.inside_brackets {
-            return None;
+    fn handle_comment_start(&mut self) -> () {
+        if !self.context.inside_brackets || self.context.current_element.is_some() {
+            return;
         }
 
-        let last_element = self.context.elements.last();
-        if last_element.is_some() 
-            && (
-                last_element.unwrap().name.is_element(HtmlElementName::Script)
-                || last_element.unwrap().name.is_element(HtmlElementName::Style)
-            )
+        let content = self.context.text_content.as_str();
+        if content == "!--" {
+            self.context.inside_comment = true;
+            self.context.text_content.clear();
+        } else if content == "?"
+            || (content.chars().count() == 2 && content.starts_with('!') && !content.ends_with(['-', 'd', 'D']))
+            || (content.chars().count() == 3 && content.starts_with("!-"))
         {
-            return None;
+            self.context.inside_bogus_comment = true;
+            self.context.text_content = content.trim_start_matches('!').to_string();
         }
+    }
+
+    fn handle_comment_character(&mut self, sign: &str) -> Option<HtmlEvent<'a>> {
+        self.context.text_content.push_str(sign);
+
+        let content = self.context.text_content.as_str();
+        let terminator_length: usize;
+        if self.context.inside_bogus_comment {
+            if sign != ">" {
+                return None;
+            }
 
-        let mut event: Option<HtmlEvent> = None;
-        let content = self.context.text_content.trim().to_string();
-        if !content.is_empty() {
-            event = Some(HtmlEvent::TextContent(content));
+            terminator_length = 1;
+        } else if content == ">" || content == "->" {
+            terminator_length = content.len();
+        } else if content.ends_with("-->") {
+            terminator_length = 3;
+        } else if content.ends_with("--!>") {
+            terminator_length = 4;
+        } else {
+            return None;
         }
 
+        let span = Span::new(self.context.tag_start, self.position_after(sign));
+        let comment = self.borrow_input(&content[0..content.len() - terminator_length], span.end.offset - terminator_length);
+        self.context.inside_brackets = false;
+        self.context.inside_comment = false;
+        self.context.inside_bogus_comment = false;
+        self.context.text_content.clear();
+
+        return Some(HtmlEvent::Comment { content: comment, span, depth: self.depth() });
+    }
+
+    /// Turns the collected declaration text into a doctype event, a doctype
+    /// broken off by the end of the source is forced into quirks mode.
+    fn take_doctype(&mut self, terminated: bool) -> HtmlEvent<'a> {
+        let doctype = Doctype::parse(&self.context.text_content);
+        let end = match terminated {
+            true => self.position_after(">"),
+            false => self.context.position
+        };
+        self.context.inside_brackets = false;
+        self.context.inside_doctype = false;
+        self.context.current_element = None;
+        self.context.text_content.clear();
         self.context.skip_content_fillup = true;
-        self.context.text_content = String::new();
+
+        return HtmlEvent::Doctype {
+            name: doctype.name.map(Cow::Owned),
+            public_id: doctype.public_id.map(Cow::Owned),
+            system_id: doctype.system_id.map(Cow::Owned),
+            force_quirks: doctype.force_quirks || !terminated,
+            span: Span::new(self.context.tag_start, end)
+        };
+    }
+
+    /// Handles a character of raw text, RCDATA or script data, which only end
+    /// at an end tag with the name of the element they started with.
+    fn handle_raw_text_character(&mut self, sign: &str) -> () {
+        let char = sign.chars().next().unwrap_or_default();
+        if let Some(start) = self.context.end_tag_start {
+            let candidate = &self.context.text_content[start..];
+            if (candidate == "<" && sign == "/") || (candidate.starts_with("</") && char.is_ascii_alphabetic()) {
+                self.context.text_content.push_str(sign);
+
+                return;
+            }
+
+            let is_end_tag = candidate.len() > 2
+                && candidate.starts_with("</")
+                && (char.is_whitespace() || sign == "/" || sign == ">")
+                && self.context.raw_text_element.as_ref().is_some_and(|element| candidate[2..].eq_ignore_ascii_case(element.to_str()));
+
+            if is_end_tag {
+                self.context.end_tag_start = None;
+                self.context.text_content.truncate(start);
+                let decode = self.options.decode_entities && self.context.text_state == TextState::RcData;
+                if let Some(text) = self.take_text_content(decode) {
+                    self.emit(text);
+                }
+
+                self.context.text_state = TextState::Data;
+                self.context.script_state = ScriptState::Unescaped;
+                self.context.inside_brackets = true;
+                self.context.is_closing_element = true;
+                self.context.current_element = self.context.raw_text_element.take();
+                if let Some(event) = self.handle_attribute_character(sign) {
+                    self.emit(event);
+                }
+
+                return;
+            }
+
+            // The spec goes back to the state before the `<` once the end tag
+            // turns out to be text.
+            if candidate.len() > 1 {
+                self.context.script_state = match self.context.script_state {
+                    ScriptState::EscapedLessThan => ScriptState::Escaped,
+                    _ => ScriptState::Unescaped
+                };
+            }
+
+            self.abandon_end_tag();
+        }
+
+        let mut starts_end_tag = sign == "<" && self.context.text_state != TextState::PlainText;
+        if self.context.text_state == TextState::ScriptData {
+            starts_end_tag &= !matches!(
+                self.context.script_state,
+                ScriptState::DoubleEscaped | ScriptState::DoubleEscapedDash | ScriptState::DoubleEscapedDashDash
+            );
+
+            self.context.script_state = self.next_script_state(self.context.script_state, char);
+        }
+
+        if starts_end_tag {
+            self.context.end_tag_start = Some(self.context.text_content.len());
+            self.context.tag_start = self.context.position;
+        } else {
+            self.extend_text_span(sign);
+        }
+
+        self.context.text_content.push_str(sign);
+    }
+
+    /// Follows the script data escape states of the spec for the next
+    /// character of a script, end tags are checked by the caller.
+    fn next_script_state(&self, state: ScriptState, char: char) -> ScriptState {
+        let is_tag_end = char.is_whitespace() || char == '/' || char == '>';
+        return match (state, char) {
+            (ScriptState::Unescaped, '<') => ScriptState::LessThan,
+            (ScriptState::Unescaped, _) => ScriptState::Unescaped,
+            (ScriptState::LessThan, '!') => ScriptState::EscapeStart,
+            (ScriptState::LessThan, _) => self.next_script_state(ScriptState::Unescaped, char),
+            (ScriptState::EscapeStart, '-') => ScriptState::EscapeStartDash,
+            (ScriptState::EscapeStartDash, '-') => ScriptState::EscapedDashDash,
+            (ScriptState::EscapeStart | ScriptState::EscapeStartDash, _) => self.next_script_state(ScriptState::Unescaped, char),
+            (ScriptState::Escaped, '-') => ScriptState::EscapedDash,
+            (ScriptState::EscapedDash | ScriptState::EscapedDashDash, '-') => ScriptState::EscapedDashDash,
+            (ScriptState::Escaped | ScriptState::EscapedDash | ScriptState::EscapedDashDash, '<') => ScriptState::EscapedLessThan,
+            (ScriptState::EscapedDashDash, '>') => ScriptState::Unescaped,
+            (ScriptState::Escaped | ScriptState::EscapedDash | ScriptState::EscapedDashDash, _) => ScriptState::Escaped,
+            (ScriptState::EscapedLessThan, _) if char.is_ascii_alphabetic() => ScriptState::DoubleEscapeStart,
+            (ScriptState::EscapedLessThan, _) => self.next_script_state(ScriptState::Escaped, char),
+            (ScriptState::DoubleEscapeStart, _) if is_tag_end => match self.names_script_tag() {
+                true => ScriptState::DoubleEscaped,
+                false => ScriptState::Escaped
+            },
+            (ScriptState::DoubleEscapeStart, _) if char.is_ascii_alphabetic() => ScriptState::DoubleEscapeStart,
+            (ScriptState::DoubleEscapeStart, _) => self.next_script_state(ScriptState::Escaped, char),
+            (ScriptState::DoubleEscaped, '-') => ScriptState::DoubleEscapedDash,
+            (ScriptState::DoubleEscapedDash | ScriptState::DoubleEscapedDashDash, '-') => ScriptState::DoubleEscapedDashDash,
+            (ScriptState::DoubleEscaped | ScriptState::DoubleEscapedDash | ScriptState::DoubleEscapedDashDash, '<') => ScriptState::DoubleEscapedLessThan,
+            (ScriptState::DoubleEscapedDashDash, '>') => ScriptState::Unescaped,
+            (ScriptState::DoubleEscaped | ScriptState::DoubleEscapedDash | ScriptState::DoubleEscapedDashDash, _) => ScriptState::DoubleEscaped,
+            (ScriptState::DoubleEscapedLessThan, '/') => ScriptState::DoubleEscapeEnd,
+            (ScriptState::DoubleEscapedLessThan, _) => self.next_script_state(ScriptState::DoubleEscaped, char),
+            (ScriptState::DoubleEscapeEnd, _) if is_tag_end => match self.names_script_tag() {
+                true => ScriptState::Escaped,
+                false => ScriptState::DoubleEscaped
+            },
+            (ScriptState::DoubleEscapeEnd, _) if char.is_ascii_alphabetic() => ScriptState::DoubleEscapeEnd,
+            (ScriptState::DoubleEscapeEnd, _) => self.next_script_state(ScriptState::DoubleEscaped, char)
+        };
+    }
+
+    /// Checks whether the tag at the end of the script text, as in `<script`
+    /// or `</script`, is named `script`.
+    fn names_script_tag(&self) -> bool {
+        let text = self.context.text_content.as_str();
+        let name = text.rfind('<').map_or("", |start| text[start + 1..].trim_start_matches('/'));
+
+        return name.eq_ignore_ascii_case("script");
+    }
+
+    /// Keeps the characters of a possible end tag as text, they are not part
+    /// of the text span yet.
+    fn abandon_end_tag(&mut self) -> () {
+        self.context.end_tag_start = None;
+        let start = self.context.tag_start;
+        let end = self.context.position;
+        self.context.text_span.get_or_insert(Span::new(start, end)).end = end;
+    }
+
+    /// Handles the character behind a `<` or `</` in data. A letter starts a
+    /// tag, `!` and `?` start markup declarations and bogus comments, `</>` is
+    /// dropped and anything else leaves the `<` as text.
+    fn handle_tag_open(&mut self, tag_open: TagOpen, sign: &str) -> () {
+        let char = sign.chars().next().unwrap_or_default();
+        match tag_open {
+            TagOpen::LessThan if sign == "/" => {
+                self.context.tag_open = Some(TagOpen::Solidus);
+
+                return;
+            }
+
+            TagOpen::LessThan if char.is_ascii_alphabetic() || char == '!' || char == '?' => self.handle_opening_bracket(),
+            TagOpen::LessThan => self.push_tag_open_text("<"),
+            TagOpen::Solidus if char.is_ascii_alphabetic() => {
+                self.handle_opening_bracket();
+                self.context.is_closing_element = true;
+            }
+
+            TagOpen::Solidus if sign == ">" => {
+                if let Some(event) = self.take_text_content(self.options.decode_entities) {
+                    self.emit(event);
+                }
+
+                return;
+            }
+
+            TagOpen::Solidus => {
+                self.handle_opening_bracket();
+                self.context.inside_bogus_comment = true;
+            }
+        }
+
+        self.handle_character(sign);
+    }
+
+    fn handle_opening_bracket(&mut self) -> () {
+        if let Some(event) = self.take_text_content(self.options.decode_entities) {
+            self.emit(event);
+        }
+
         self.context.inside_brackets = true;
+    }
+
+    /// Keeps a `<` or `</` that does not start a tag as text.
+    fn push_tag_open_text(&mut self, text: &str) -> () {
+        let start = self.context.tag_start;
+        let mut end = start;
+        end.advance_str(text);
+        self.context.text_span.get_or_insert(Span::new(start, end)).end = end;
+        self.context.text_content.push_str(text);
+    }
+
+    /// Turns the text collected since the last tag into an event.
+    fn take_text_content(&mut self, decode: bool) -> Option<HtmlEvent<'a>> {
+        let mut event: Option<HtmlEvent<'a>> = None;
+        let span = self.context.text_span.take().unwrap_or_default();
+        let text = self.context.text_content.as_str();
+        let text = match self.whitespace_mode() {
+            WhitespaceMode::Preserve => Cow::Borrowed(text),
+            WhitespaceMode::Collapse => collapse_whitespace(text, false),
+            WhitespaceMode::Trim => collapse_whitespace(text, true)
+        };
+
+        if !text.is_empty() {
+            let mut content = self.borrow_input(&text, span.end.offset);
+            if decode {
+                content = decode_entities(content, false);
+            }
+
+            event = Some(HtmlEvent::TextContent { content, span, depth: self.depth() });
+        }
+
+        self.context.text_content.clear();
 
         return event;
     }
 
-    pub fn next(&mut self) -> Result<HtmlEvent, &'static str> {
-        if self.context.events.len() > 0 {
-            let event = self.context.events.pop_front().unwrap();
-            
-            return Ok(event);
+    /// Whitespace mode for the text being collected.
+    fn whitespace_mode(&self) -> WhitespaceMode {
+        return match self.context.text_state {
+            TextState::Data => self.options.whitespace,
+            _ => WhitespaceMode::Preserve
+        };
+    }
+
+    fn extend_text_span(&mut self, sign: &str) -> () {
+        let start = self.context.position;
+        let end = self.position_after(sign);
+        let text_span = self.context.text_span.get_or_insert(Span::new(start, end));
+        if sign != "<" {
+            text_span.end = end;
+        }
+    }
+
+    /// Queues an event of the tokenizer, in tree construction mode the tree
+    /// builder queues the resulting events instead.
+    fn emit(&mut self, event: HtmlEvent<'a>) -> () {
+        if self.context.quirks_mode.is_none() {
+            self.context.quirks_mode = match &event {
+                HtmlEvent::Doctype { name, public_id, system_id, force_quirks, .. } => {
+                    Some(doctype::quirks_mode(name.as_deref(), public_id.as_deref(), system_id.as_deref(), *force_quirks))
+                }
+
+                HtmlEvent::Comment { .. } => None,
+                _ => Some(QuirksMode::Quirks)
+            };
         }
 
-        loop {
-            let mut buffer = [0; 4];
-            let read_result = self.source.read(&mut buffer).unwrap();
-            if read_result == 0 {
-                if self.context.events.len() > 0 {
-                    let event = self.context.events.pop_front().unwrap();
-                    
-                    return Ok(event);
+        match self.context.tree.as_mut() {
+            Some(tree) => tree.process(event, &mut self.context.events),
+            None => self.context.events.push_back(Ok(event))
+        }
+    }
+
+    fn report_error(&mut self, error: ParseError) -> () {
+        if self.options.strict {
+            self.context.events.push_back(Err(error));
+        }
+    }
+
+    fn handle_character(&mut self, sign: &str) -> () {
+        if self.context.inside_comment || self.context.inside_bogus_comment {
+            if let Some(comment) = self.handle_comment_character(sign) {
+                self.emit(comment);
+            }
+
+            return;
+        }
+
+        if self.context.inside_doctype {
+            if sign == ">" {
+                let doctype = self.take_doctype(true);
+                self.emit(doctype);
+            } else {
+                self.context.text_content.push_str(sign);
+            }
+
+            return;
+        }
+
+        if self.context.text_state != TextState::Data {
+            self.handle_raw_text_character(sign);
+
+            return;
+        }
+
+        if let Some(tag_open) = self.context.tag_open.take() {
+            self.handle_tag_open(tag_open, sign);
+
+            return;
+        }
+
+        if self.context.inside_brackets && self.context.current_element.is_some() {
+            if let Some(event) = self.handle_attribute_character(sign) {
+                self.emit(event);
+            }
+
+            return;
+        }
+
+        let mut event: Option<HtmlEvent<'a>> = None;
+        self.context.skip_content_fillup = false;
+        let char = sign.chars().next();
+        if sign == "/" {
+            self.handle_closing_element();
+        } else if sign == "<" && !self.context.inside_brackets {
+            self.context.tag_start = self.context.position;
+            self.context.tag_open = Some(TagOpen::LessThan);
+
+            return;
+        } else if sign == ">" && self.context.inside_brackets {
+            event = self.handle_closing_bracket();
+        } else if char.is_some_and(|char| char.is_whitespace()) {
+            event = self.handle_whitespace();
+        }
+
+        if let Some(event) = event {
+            self.emit(event);
+
+            return;
+        }
+
+        if !self.context.skip_content_fillup {
+            self.context.text_content.push_str(sign);
+            let is_whitespace = char.is_some_and(|char| char.is_whitespace());
+            if !self.context.inside_brackets && (!is_whitespace || self.whitespace_mode() != WhitespaceMode::Trim) {
+                self.extend_text_span(sign);
+            }
+
+            self.handle_comment_start();
+        }
+    }
+
+    /// Consumes a run of characters that cannot change the tokenizer state in
+    /// one go, returns the length of the consumed run in bytes.
+    fn handle_run(&mut self, text: &str) -> usize {
+        if self.context.tag_open.is_some() {
+            return 0;
+        }
+
+        if self.context.inside_comment || self.context.inside_bogus_comment || self.context.inside_doctype {
+            let length = text.find('>').unwrap_or(text.len());
+            self.context.text_content.push_str(&text[0..length]);
+
+            return length;
+        }
+
+        if self.context.inside_brackets && self.context.current_element.is_none() {
+            let is_tag_name = match self.context.text_content.as_bytes().first() {
+                Some(byte) => byte.is_ascii_alphabetic(),
+                None => text.as_bytes()[0].is_ascii_alphabetic()
+            };
+
+            if !is_tag_name {
+                return 0;
+            }
+
+            let length = ascii_run_length(text, |byte| matches!(byte, b'/' | b'<' | b'>'));
+            self.context.text_content.push_str(&text[0..length]);
+
+            return length;
+        }
+
+        if self.context.inside_brackets && self.context.attribute_state == AttributeState::Name {
+            let length = ascii_run_length(text, |byte| matches!(byte, b'/' | b'<' | b'>' | b'=' | b'"' | b'\''));
+            if length > 0 {
+                self.context.attribute_name.push_str(&text[0..length].to_ascii_lowercase());
+                self.context.attribute_span.name.end.advance_str(&text[0..length]);
+            }
+
+            return length;
+        }
+
+        if self.context.inside_brackets && self.context.attribute_state == AttributeState::UnquotedValue {
+            let length = ascii_run_length(text, |byte| matches!(byte, b'<' | b'>' | b'=' | b'"' | b'\'' | b'`'));
+            if length > 0 {
+                self.context.attribute_value.push_str(&text[0..length]);
+                if let Some(value_span) = self.context.attribute_span.value.as_mut() {
+                    value_span.end.advance_str(&text[0..length]);
                 }
+            }
 
-                return Ok(HtmlEvent::HtmlDocumentEnd);
+            return length;
+        }
+
+        if self.context.inside_brackets {
+            let quote = match self.context.attribute_state {
+                AttributeState::DoubleQuotedValue => '"',
+                AttributeState::SingleQuotedValue => '\'',
+                _ => return 0
+            };
+
+            let length = text.find(quote).unwrap_or(text.len());
+            if length > 0 {
+                let mut end = self.context.position;
+                end.advance_str(&text[0..length]);
+                if let Some(value_span) = self.context.attribute_span.value.as_mut() {
+                    value_span.end = end;
+                }
+
+                self.context.attribute_value.push_str(&text[0..length]);
             }
 
-            if self.context.buffer_vec.is_empty() {
-                self.context.buffer_vec = Vec::from(buffer);
+            return length;
+        }
+
+        // Inside of a possible end tag of raw text or an escaped script every
+        // character counts.
+        if self.context.end_tag_start.is_some() || self.context.script_state != ScriptState::Unescaped {
+            return 0;
+        }
+
+        let length = text.find('<').unwrap_or(text.len());
+        let run = &text[0..length];
+        let is_trimmed = self.whitespace_mode() == WhitespaceMode::Trim;
+        if let Some(first) = run.find(|char: char| !is_trimmed || !char.is_whitespace()) {
+            let last = run.rfind(|char: char| !is_trimmed || !char.is_whitespace()).unwrap();
+            let last_end = last + run[last..].chars().next().unwrap().len_utf8();
+            let mut start = self.context.position;
+            start.advance_str(&run[0..first]);
+            let mut end = start;
+            end.advance_str(&run[first..last_end]);
+
+            let text_span = self.context.text_span.get_or_insert(Span::new(start, end));
+            text_span.end = end;
+        }
+
+        self.context.text_content.push_str(run);
+
+        return length;
+    }
+
+    /// Tokenizes `text` until an event is queued, returns the number of
+    /// bytes consumed. Stopping at events keeps the open-element stack in step
+    /// with the events handed out by `next`.
+    fn handle_text(&mut self, text: &str) -> usize {
+        let mut rest = text;
+        while self.context.events.is_empty() {
+            let Some(char) = rest.chars().next() else {
+                break;
+            };
+
+            let mut length = self.handle_run(rest);
+            if length == 0 {
+                length = char.len_utf8();
+                self.handle_character(&rest[0..length]);
+                self.context.position.advance(&rest[0..length]);
             } else {
-                let mut temporary_vec = Vec::from(buffer);
-                self.context.buffer_vec.append(&mut temporary_vec);
+                self.context.position.advance_str(&rest[0..length]);
+            }
+
+            rest = &rest[length..];
+        }
+
+        return text.len() - rest.len();
+    }
+
+    /// Tokenizes buffered source text until an event is queued or the buffer
+    /// runs dry. An incomplete UTF-8 sequence at the end of the buffer waits
+    /// for the next read unless the source has ended, invalid sequences are
+    /// handled as `HtmlParserOptions::invalid_utf8` says.
+    fn handle_buffer(&mut self, is_eof: bool) -> () {
+        while self.context.events.is_empty() {
+            if self.context.text_consumed < self.context.text_buffer.len() {
+                let text = std::mem::take(&mut self.context.text_buffer);
+                self.context.text_consumed += self.handle_text(&text[self.context.text_consumed..]);
+                self.context.text_buffer = text;
+
+                continue;
             }
 
-            let read_bytes_result = String::from_utf8(self.context.buffer_vec.clone());
-            let read_bytes: String;
-            match read_bytes_result {
-                Ok(element) => {
-                    read_bytes = element.clone();
-                    self.context.buffer_vec = vec![];
+            self.context.text_buffer.clear();
+            self.context.text_consumed = 0;
+            if self.context.buffer_vec.is_empty() {
+                return;
+            }
+
+            let encoding = self.encoding();
+            if encoding != Encoding::Utf8 {
+                let length = encoding.decode(&self.context.buffer_vec, is_eof, &mut self.context.text_buffer);
+                self.context.buffer_vec.drain(0..length);
+                if length == 0 {
+                    return;
                 }
 
-                Err(_error) => {
+                continue;
+            }
+
+            let error = match std::str::from_utf8(&self.context.buffer_vec) {
+                Ok(text) => {
+                    self.context.text_buffer.push_str(text);
+                    self.context.buffer_vec.clear();
+
                     continue;
                 }
+
+                Err(error) => error
+            };
+
+            let valid_up_to = error.valid_up_to();
+            if valid_up_to > 0 {
+                let text = std::str::from_utf8(&self.context.buffer_vec[0..valid_up_to]).unwrap();
+                self.context.text_buffer.push_str(text);
+                self.context.buffer_vec.drain(0..valid_up_to);
+
+                continue;
+            }
+
+            let length = match error.error_len() {
+                Some(length) => length,
+                None if is_eof => self.context.buffer_vec.len(),
+                None => return
+            };
+
+            match self.options.invalid_utf8 {
+                InvalidUtf8::Replace => self.context.text_buffer.push(char::REPLACEMENT_CHARACTER),
+                InvalidUtf8::Latin1 => {
+                    let bytes = &self.context.buffer_vec[0..length];
+                    self.context.text_buffer.extend(bytes.iter().map(|byte| char::from(*byte)));
+                }
+
+                InvalidUtf8::Error => {
+                    let position = self.context.position;
+                    self.context.events.push_back(Err(ParseError::InvalidUtf8 { position }));
+                    self.context.position.offset += length;
+                    self.context.position.column += 1;
+                }
+            }
+
+            self.context.buffer_vec.drain(0..length);
+        }
+    }
+
+    /// Decides the encoding from the first bytes of the source, returns the
+    /// length of the byte order mark. Offsets of events start behind the byte
+    /// order mark.
+    fn sniff_encoding(&mut self, bytes: &[u8]) -> usize {
+        let (encoding, confidence, byte_order_mark) = encoding::sniff(bytes, self.options.transport_encoding);
+        self.context.encoding = Some((encoding, confidence));
+        self.context.position.offset = byte_order_mark;
+
+        return byte_order_mark;
+    }
+
+    fn handle_document_end(&mut self) -> () {
+        match self.context.tag_open.take() {
+            Some(TagOpen::LessThan) => self.push_tag_open_text("<"),
+            Some(TagOpen::Solidus) => self.push_tag_open_text("</"),
+            None => {}
+        }
+
+        if self.context.inside_comment || self.context.inside_bogus_comment {
+            let span = Span::new(self.context.tag_start, self.context.position);
+            let content = self.borrow_input(&self.context.text_content, span.end.offset);
+            self.context.text_content.clear();
+            self.context.inside_comment = false;
+            self.context.inside_bogus_comment = false;
+            let depth = self.depth();
+            self.emit(HtmlEvent::Comment { content, span, depth });
+        } else if self.context.inside_brackets
+            && self.fill_element_from_text_content().is_some_and(|name| name.is_element(HtmlElementName::Doctype))
+        {
+            let doctype = self.take_doctype(false);
+            self.emit(doctype);
+            let position = self.context.tag_start;
+            self.report_error(ParseError::UnexpectedEof { position });
+        } else if self.context.inside_brackets {
+            let position = self.context.tag_start;
+            self.report_error(ParseError::UnexpectedEof { position });
+        } else {
+            if self.context.end_tag_start.is_some() {
+                self.abandon_end_tag();
+            }
+
+            let decode = self.options.decode_entities && matches!(self.context.text_state, TextState::Data | TextState::RcData);
+            if let Some(text) = self.take_text_content(decode) {
+                self.emit(text);
+            }
+        }
+
+        if let Some(tree) = self.context.tree.as_mut() {
+            tree.finish(self.context.position, &mut self.context.events);
+        }
+
+        self.context.is_finished = true;
+    }
+
+    /// Emits the text read before a failed read, followed by the error. The
+    /// document ends there.
+    fn handle_read_error(&mut self, error: std::io::Error) -> () {
+        if !(self.context.inside_brackets || self.context.inside_comment || self.context.inside_bogus_comment) {
+            let decode = self.options.decode_entities && matches!(self.context.text_state, TextState::Data | TextState::RcData);
+            if let Some(text) = self.take_text_content(decode) {
+                self.emit(text);
+            }
+        }
+
+        let position = self.context.position;
+        self.context.events.push_back(Err(ParseError::Io { error, position }));
+        self.context.is_finished = true;
+    }
+
+    pub(crate) fn source_mut(&mut self) -> &mut R {
+        return &mut self.source;
+    }
+
+    /// Position of the next character to be tokenized.
+    pub(crate) fn position(&self) -> Position {
+        return self.context.position;
+    }
+
+    /// Elements opened but not closed yet at the last returned event,
+    /// outermost first. After an `HtmlElementOpened` event the opened element
+    /// is the last one, unless it is a void or self-closing foreign element.
+    pub fn ancestors(&self) -> &[HtmlElement<'a>] {
+        return match &self.context.tree {
+            Some(_) => &self.context.returned_elements,
+            None => self.context.elements.as_slice()
+        };
+    }
+
+    /// Elements open at the token being tokenized, ahead of the returned
+    /// events in tree construction mode.
+    fn open_elements(&self) -> &[HtmlElement<'a>] {
+        return match &self.context.tree {
+            Some(tree) => tree.open_elements(),
+            None => self.context.elements.as_slice()
+        };
+    }
+
+    /// Follows the elements opened and closed by a returned event in tree
+    /// construction mode, where the depths of the events are final.
+    fn track_returned_event(&mut self, event: &HtmlEvent<'a>) -> () {
+        match event {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                self.context.returned_elements.truncate(opened_element.depth);
+                if opened_element.namespace != Namespace::Html || !opened_element.name.is_self_closing_element() {
+                    self.context.returned_elements.push(opened_element.clone());
+                }
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                self.context.returned_elements.truncate(closed_element.depth);
             }
 
-            let mut event: Option<HtmlEvent>;
-            for sign_im in read_bytes.split("").into_iter() {
-                let mut sign = sign_im.clone();
-                event = None;
-                self.context.skip_content_fillup = false;
-                let char = sign.chars().next();
-                if sign == "/" {
-                    event = self.handle_closing_element();
-                } else if sign == "<" {
-                    event = self.handle_opening_bracket();
-                } else if sign == ">" && self.context.inside_brackets {
-                    event = self.handle_closing_bracket();
-                } else if char.is_some() && char.unwrap().is_whitespace() {
-                    event = self.handle_whitespace();
-                    sign = " ";
+            _ => {}
+        }
+    }
+
+    /// Document mode set by the doctype, quirks mode for documents that do not
+    /// start with one. No-quirks mode until the first element or text.
+    pub fn quirks_mode(&self) -> QuirksMode {
+        return self.context.quirks_mode.unwrap_or_default();
+    }
+
+    /// Encoding the source is decoded with, UTF-8 until it has been sniffed
+    /// by the first call to `next`. For sources not in UTF-8, spans count the
+    /// bytes of the text transcoded to UTF-8.
+    pub fn encoding(&self) -> Encoding {
+        return self.context.encoding.map_or(Encoding::Utf8, |(encoding, _)| encoding);
+    }
+
+    pub fn encoding_confidence(&self) -> Confidence {
+        return self.context.encoding.map_or(Confidence::Tentative, |(_, confidence)| confidence);
+    }
+
+    /// Number of elements in `ancestors`.
+    pub fn depth(&self) -> usize {
+        return self.ancestors().len();
+    }
+
+    /// The ancestors as a selector-like path, e.g. `html > body > div#app.main`.
+    pub fn path(&self) -> String {
+        let segments: Vec<String> = self.ancestors().iter().map(path_segment).collect();
+
+        return segments.join(" > ");
+    }
+
+    /// Returns `text` borrowed from the input when the input holds the same
+    /// text ending at byte offset `end`, an owned copy otherwise.
+    fn borrow_input(&self, text: &str, end: usize) -> Cow<'a, str> {
+        let borrowed = self.input
+            .and_then(|input| input.get(end.checked_sub(text.len())?..end))
+            .filter(|borrowed| *borrowed == text);
+
+        return match borrowed {
+            Some(borrowed) => Cow::Borrowed(borrowed),
+            None => Cow::Owned(text.to_string())
+        };
+    }
+
+    fn handle_input(&mut self, input: &'a str) -> () {
+        let start = self.context.position.offset;
+        if start == input.len() {
+            self.handle_document_end();
+
+            return;
+        }
+
+        self.handle_text(&input[start..]);
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<HtmlEvent<'a>, ParseError> {
+        loop {
+            if let Some(event) = self.context.events.pop_front() {
+                if let (Some(_), Ok(event)) = (&self.context.tree, &event) {
+                    self.track_returned_event(event);
                 }
 
-                if event.is_some() {
-                    self.context.events.push_back(event.unwrap());
+                return event;
+            }
 
+            if self.context.is_finished {
+                return Ok(HtmlEvent::HtmlDocumentEnd);
+            }
+
+            if let Some(input) = self.input {
+                self.handle_input(input);
+
+                continue;
+            }
+
+            if self.context.encoding.is_none() {
+                let needed = match self.options.transport_encoding {
+                    Some(_) => 3,
+                    None => PRESCAN_LENGTH
+                };
+
+                if self.context.source_ended || self.context.read_error.is_some() || self.context.buffer_vec.len() >= needed {
+                    let bytes = std::mem::take(&mut self.context.buffer_vec);
+                    let byte_order_mark = self.sniff_encoding(&bytes);
+                    self.context.buffer_vec = bytes;
+                    self.context.buffer_vec.drain(0..byte_order_mark);
+                }
+            }
+
+            if self.context.encoding.is_some() {
+                self.handle_buffer(self.context.source_ended);
+                if !self.context.events.is_empty() {
                     continue;
                 }
 
-                if !self.context.skip_content_fillup {
-                    if sign == " " {
-                        self.context.text_content = self.context.text_content.trim().to_string();
-                    }
+                if self.context.source_ended {
+                    self.handle_document_end();
 
-                    self.context.text_content.push_str(sign);
+                    continue;
                 }
             }
 
-            if self.context.events.len() > 0 {
-                let event = self.context.events.pop_front().unwrap();
-                
-                return Ok(event);
+            if let Some(error) = self.context.read_error.take() {
+                self.handle_read_error(error);
+
+                continue;
+            }
+
+            if self.context.read_buffer.is_empty() {
+                self.context.read_buffer.resize(CHUNK_SIZE, 0);
             }
+
+            match self.source.read(&mut self.context.read_buffer) {
+                Ok(0) => self.context.source_ended = true,
+                Ok(length) => self.context.buffer_vec.extend_from_slice(&self.context.read_buffer[..length]),
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                // The bytes held back for sniffing are tokenized first.
+                Err(error) if self.context.encoding.is_none() => self.context.read_error = Some(error),
+                Err(error) => self.handle_read_error(error)
+            }
+        }
+    }
+}
+
+/// Tokenizer state for the content of an HTML element named `name`.
+fn text_state_of(name: &HtmlElementName) -> TextState {
+    return match name.to_str() {
+        "textarea" | "title" => TextState::RcData,
+        "style" | "xmp" | "iframe" | "noembed" | "noframes" => TextState::RawText,
+        "script" => TextState::ScriptData,
+        "plaintext" => TextState::PlainText,
+        _ => TextState::Data
+    };
+}
+
+fn path_segment(element: &HtmlElement) -> String {
+    let mut segment = element.name.to_str().to_string();
+    if let Some(id) = element.attributes.get("id") {
+        segment.push('#');
+        segment.push_str(id);
+    }
+
+    for class in element.attributes.get("class").map_or("", |value| value.as_ref()).split_whitespace() {
+        segment.push('.');
+        segment.push_str(class);
+    }
+
+    return segment;
+}
+
+/// Length of the leading ASCII run of `text` that contains neither whitespace
+/// nor any of the given delimiters. Non-ASCII characters end the run so that
+/// Unicode whitespace keeps going through the character handlers.
+fn ascii_run_length(text: &str, is_delimiter: fn(u8) -> bool) -> usize {
+    return text
+        .as_bytes()
+        .iter()
+        .position(|byte| !byte.is_ascii() || byte.is_ascii_whitespace() || *byte == b'\x0b' || is_delimiter(*byte))
+        .unwrap_or(text.len());
+}
+
+fn collapse_whitespace(text: &str, trim: bool) -> Cow<'_, str> {
+    let text = if trim { text.trim() } else { text };
+    let mut after_space = false;
+    let is_collapsed = text.chars().all(|char| {
+        let is_valid = !char.is_whitespace() || (char == ' ' && !after_space);
+        after_space = char.is_whitespace();
+
+        return is_valid;
+    });
+
+    if is_collapsed {
+        return Cow::Borrowed(text);
+    }
+
+    let mut collapsed = String::with_capacity(text.len());
+    after_space = false;
+    for char in text.chars() {
+        if !char.is_whitespace() {
+            collapsed.push(char);
+        } else if !after_space {
+            collapsed.push(' ');
+        }
+
+        after_space = char.is_whitespace();
+    }
+
+    return Cow::Owned(collapsed);
+}
+
+fn decode_entities(text: Cow<'_, str>, in_attribute: bool) -> Cow<'_, str> {
+    return match text {
+        Cow::Borrowed(text) => entities::decode(text, in_attribute),
+        Cow::Owned(text) => match entities::decode(&text, in_attribute) {
+            Cow::Borrowed(_) => Cow::Owned(text),
+            Cow::Owned(decoded) => Cow::Owned(decoded)
+        }
+    };
+}
+
+impl<'a, R:Read> Iterator for HtmlParser<'a, R> {
+    type Item = Result<HtmlEvent<'a>, ParseError>;
+
+    /// Yields events until the end of the document, `HtmlEvent::HtmlDocumentEnd`
+    /// itself is not yielded.
+    fn next(&mut self) -> Option<Self::Item> {
+        match HtmlParser::next(self) {
+            Ok(HtmlEvent::HtmlDocumentEnd) => None,
+            event => Some(event)
         }
     }
 }
 
+impl<'a, R:Read> FusedIterator for HtmlParser<'a, R> {}
@@ -0,0 +1,65 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Byte offset from the start of the source.
+    pub offset: usize,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column number in characters, starting at 1.
+    pub column: usize
+}
+
+impl Position {
+    pub fn new() -> Position {
+        return Position { offset: 0, line: 1, column: 1 };
+    }
+
+    pub fn advance(&mut self, sign: &str) -> () {
+        self.offset += sign.len();
+        if sign == "\n" {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+
+    pub fn advance_str(&mut self, text: &str) -> () {
+        self.offset += text.len();
+        match text.rfind('\n') {
+            Some(index) => {
+                self.line += text.as_bytes().iter().filter(|byte| **byte == b'\n').count();
+                self.column = text[index + 1..].chars().count() + 1;
+            }
+
+            None => self.column += text.chars().count()
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        return Position::new();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Position,
+    /// Position right after the last character of the span.
+    pub end: Position
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        return Span { start, end };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AttributeSpan {
+    pub name: Span,
+    /// Span of the value without its quotes, `None` for attributes without a value.
+    pub value: Option<Span>
+}
@@ -0,0 +1,344 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+
+use crate::element::{HtmlElement, HtmlElementName};
+use crate::encoding::{Encoding, InvalidUtf8};
+use crate::error::{ParseError, SelectorError};
+use crate::selector::Selector;
+use crate::writer::write_start_tag;
+use crate::{HtmlEvent, HtmlParser, HtmlParserOptions};
+
+/// An element matched by a rewriter handler. Changes made by the handler are
+/// applied to the output, inserted content is written as HTML.
+pub struct Element<'a> {
+    element: HtmlElement<'a>,
+    attributes_changed: bool,
+    before: String,
+    after: String,
+    prepend: String,
+    append: String,
+    inner_content: Option<String>,
+    replacement: Option<String>
+}
+
+impl<'a> Element<'a> {
+    pub fn name(&self) -> &HtmlElementName {
+        return &self.element.name;
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        return self.element.attributes.get(name).map(|value| value.as_ref());
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> () {
+        self.element.attributes.insert(Cow::Owned(name.to_ascii_lowercase()), Cow::Owned(value.to_string()));
+        self.attributes_changed = true;
+    }
+
+    pub fn remove_attribute(&mut self, name: &str) -> () {
+        self.attributes_changed |= self.element.attributes.remove(name).is_some();
+    }
+
+    pub fn before(&mut self, html: &str) -> () {
+        self.before.push_str(html);
+    }
+
+    pub fn after(&mut self, html: &str) -> () {
+        self.after.push_str(html);
+    }
+
+    /// Inserts `html` right after the start tag. Ignored for void elements.
+    pub fn prepend(&mut self, html: &str) -> () {
+        self.prepend.push_str(html);
+    }
+
+    /// Inserts `html` right before the end tag. Ignored for void elements.
+    pub fn append(&mut self, html: &str) -> () {
+        self.append.push_str(html);
+    }
+
+    /// Replaces the content of the element, keeping its tags.
+    pub fn set_inner_content(&mut self, html: &str) -> () {
+        self.inner_content = Some(html.to_string());
+    }
+
+    /// Replaces the element together with its content.
+    pub fn replace(&mut self, html: &str) -> () {
+        self.replacement = Some(html.to_string());
+    }
+
+    pub fn remove(&mut self) -> () {
+        self.replacement = Some(String::new());
+    }
+
+    pub fn is_removed(&self) -> bool {
+        return self.replacement.as_ref().is_some_and(|replacement| replacement.is_empty());
+    }
+}
+
+/// Records everything read from the source, so that the rewriter can copy the
+/// parts it does not change byte for byte.
+struct RecordingReader<R> {
+    source: R,
+    recorded: Vec<u8>,
+    /// Source offset of `recorded[0]`.
+    recorded_start: usize
+}
+
+impl<R:Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.source.read(buf)?;
+        self.recorded.extend_from_slice(&buf[0..length]);
+
+        return Ok(length);
+    }
+}
+
+type ElementHandler<'h> = Box<dyn FnMut(&mut Element) + 'h>;
+
+/// End of an element the rewriter still has to act on.
+struct PendingElement {
+    name: HtmlElementName,
+    /// Open-element stack depth with the element on top.
+    depth: usize,
+    append: String,
+    after: String,
+    /// The content is replaced or removed and must not be copied.
+    skip_content: bool,
+    /// The end tag is removed together with the element.
+    skip_end_tag: bool
+}
+
+/// Streams a document from a reader to a writer, letting handlers registered
+/// for selectors change the matched elements on the way. Everything that is
+/// not changed is copied from the source as is.
+///
+/// Selectors are limited to the subset supported by `HtmlFilter`, content of
+/// removed or replaced elements is not passed to handlers.
+pub struct HtmlRewriter<'h, W> {
+    destination: W,
+    handlers: Vec<(Selector, ElementHandler<'h>)>,
+    /// Source offset up to which the output is written.
+    written: usize
+}
+
+impl<'h, W:Write> HtmlRewriter<'h, W> {
+    pub fn new(destination: W) -> HtmlRewriter<'h, W> {
+        return HtmlRewriter { destination, handlers: vec![], written: 0 };
+    }
+
+    pub fn into_inner(self) -> W {
+        return self.destination;
+    }
+
+    /// Registers `handler` for elements matching `selectors`. Handlers run in
+    /// the order they were registered, selectors match the element as it is in
+    /// the source.
+    pub fn on(&mut self, selectors: &str, handler: impl FnMut(&mut Element) + 'h) -> Result<(), SelectorError> {
+        let selector = Selector::parse(selectors)?;
+        if !selector.is_streamable() {
+            return Err(SelectorError { message: "selector cannot be matched while streaming", offset: 0 });
+        }
+
+        self.handlers.push((selector, Box::new(handler)));
+
+        return Ok(());
+    }
+
+    /// Copies the source up to `offset` into the output.
+    fn copy_until<R>(&mut self, reader: &mut RecordingReader<R>, offset: usize) -> io::Result<()> {
+        if offset > self.written {
+            let start = self.written - reader.recorded_start;
+            self.destination.write_all(&reader.recorded[start..offset - reader.recorded_start])?;
+            self.written = offset;
+        }
+
+        // Keep the recording from growing with the document.
+        let consumed = self.written - reader.recorded_start;
+        if consumed >= 64 * 1024 {
+            reader.recorded.drain(0..consumed);
+            reader.recorded_start = self.written;
+        }
+
+        return Ok(());
+    }
+
+    fn skip_until<R>(&mut self, reader: &mut RecordingReader<R>, offset: usize) -> io::Result<()> {
+        self.written = self.written.max(offset);
+
+        return self.copy_until(reader, offset);
+    }
+
+    /// Rewrites `source` into the destination. The source is read as UTF-8,
+    /// as the output is spliced together from its bytes. Invalid sequences are
+    /// copied as they are.
+    pub fn rewrite<R:Read>(&mut self, source: R) -> io::Result<()> {
+        let options = HtmlParserOptions {
+            transport_encoding: Some(Encoding::Utf8),
+            invalid_utf8: InvalidUtf8::Error,
+            ..HtmlParserOptions::default()
+        };
+
+        let reader = RecordingReader { source, recorded: vec![], recorded_start: 0 };
+        let mut parser = HtmlParser::with_options(reader, options);
+        let mut pending: Vec<PendingElement> = vec![];
+        let mut event_end = 0;
+        self.written = 0;
+
+        loop {
+            // Parsed events nothing is pending for are written right away, so
+            // the output streams and the recording stays small.
+            let reader = parser.source_mut();
+            if pending.iter().any(|element| element.skip_content) {
+                self.skip_until(reader, event_end)?;
+            } else {
+                self.copy_until(reader, event_end)?;
+            }
+
+            let event = match parser.next() {
+                Ok(event) => event,
+                Err(ParseError::Io { error, .. }) => return Err(error),
+                Err(_) => continue
+            };
+
+            event_end = match &event {
+                HtmlEvent::HtmlElementOpened { opened_element } => opened_element.span.end.offset,
+                HtmlEvent::HtmlElementClosed { closed_element } => closed_element.span.end.offset,
+                HtmlEvent::TextContent { span, .. } | HtmlEvent::Comment { span, .. } | HtmlEvent::Doctype { span, .. } => span.end.offset,
+                HtmlEvent::HtmlDocumentEnd => event_end
+            };
+
+            let depth = parser.ancestors().len();
+            match event {
+                HtmlEvent::HtmlElementClosed { closed_element } => {
+                    // The closing tag of an ancestor closes the pending
+                    // elements opened inside of it, its own tag is kept.
+                    while pending.last().is_some_and(|element| depth < element.depth) {
+                        let element = pending.pop().unwrap();
+                        let is_end_tag = depth + 1 == element.depth && closed_element.name.to_str() == element.name.to_str();
+                        let reader = parser.source_mut();
+                        if element.skip_content {
+                            self.skip_until(reader, closed_element.span.start.offset)?;
+                        } else {
+                            self.copy_until(reader, closed_element.span.start.offset)?;
+                        }
+
+                        if !element.skip_end_tag {
+                            self.destination.write_all(element.append.as_bytes())?;
+                        }
+
+                        if is_end_tag && element.skip_end_tag {
+                            self.skip_until(reader, closed_element.span.end.offset)?;
+                        } else if is_end_tag {
+                            self.copy_until(reader, closed_element.span.end.offset)?;
+                        }
+
+                        self.destination.write_all(element.after.as_bytes())?;
+                    }
+                }
+
+                HtmlEvent::HtmlElementOpened { opened_element } => {
+                    if pending.iter().any(|element| element.skip_content) {
+                        continue;
+                    }
+
+                    let open_elements = parser.ancestors();
+                    let is_open = open_elements.last().is_some_and(|last| last.span == opened_element.span);
+                    let ancestors = if is_open { &open_elements[0..depth - 1] } else { open_elements };
+
+                    let mut element: Option<Element> = None;
+                    for (selector, handler) in self.handlers.iter_mut() {
+                        if !selector.matches_path(&opened_element, ancestors) {
+                            continue;
+                        }
+
+                        let element = element.get_or_insert_with(|| Element {
+                            element: opened_element.clone(),
+                            attributes_changed: false,
+                            before: String::new(),
+                            after: String::new(),
+                            prepend: String::new(),
+                            append: String::new(),
+                            inner_content: None,
+                            replacement: None
+                        });
+
+                        handler(element);
+                    }
+
+                    let Some(element) = element else {
+                        continue;
+                    };
+
+                    let reader = parser.source_mut();
+                    let span = opened_element.span;
+                    self.copy_until(reader, span.start.offset)?;
+                    self.destination.write_all(element.before.as_bytes())?;
+
+                    if let Some(replacement) = element.replacement {
+                        self.destination.write_all(replacement.as_bytes())?;
+                        self.skip_until(reader, span.end.offset)?;
+                        if is_open {
+                            pending.push(PendingElement { name: opened_element.name.clone(), depth, append: String::new(), after: element.after, skip_content: true, skip_end_tag: true });
+                        } else {
+                            self.destination.write_all(element.after.as_bytes())?;
+                        }
+
+                        continue;
+                    }
+
+                    if element.attributes_changed {
+                        write_start_tag(&mut self.destination, &element.element, element.element.self_closing, true)?;
+                        self.skip_until(reader, span.end.offset)?;
+                    } else {
+                        self.copy_until(reader, span.end.offset)?;
+                    }
+
+                    if !is_open {
+                        self.destination.write_all(element.after.as_bytes())?;
+
+                        continue;
+                    }
+
+                    self.destination.write_all(element.prepend.as_bytes())?;
+                    if let Some(inner_content) = &element.inner_content {
+                        self.destination.write_all(inner_content.as_bytes())?;
+                    }
+
+                    pending.push(PendingElement {
+                        name: opened_element.name.clone(),
+                        depth,
+                        append: element.append,
+                        after: element.after,
+                        skip_content: element.inner_content.is_some(),
+                        skip_end_tag: false
+                    });
+                }
+
+                HtmlEvent::HtmlDocumentEnd => {
+                    break;
+                }
+
+                _ => {}
+            }
+        }
+
+        // Elements left open at the end of the document end with it.
+        let reader = parser.source_mut();
+        let end = reader.recorded_start + reader.recorded.len();
+        if pending.iter().any(|element| element.skip_content) {
+            self.skip_until(reader, end)?;
+        } else {
+            self.copy_until(reader, end)?;
+        }
+
+        for element in pending.into_iter().rev() {
+            self.destination.write_all(element.append.as_bytes())?;
+            self.destination.write_all(element.after.as_bytes())?;
+        }
+
+        return self.destination.flush();
+    }
+}
@@ -0,0 +1,692 @@
+#![allow(clippy::needless_return)]
+
+use crate::dom::{Document, NodeData, NodeId};
+use crate::element::{HtmlElement, Namespace};
+use crate::error::SelectorError;
+
+/// A parsed CSS selector list, such as `div.card > a[href^='https'], nav a`.
+///
+/// Supports type, universal, class, id and attribute selectors, the
+/// descendant, child and sibling combinators and the `:first-child`,
+/// `:last-child`, `:only-child`, `:nth-child()`, `:nth-last-child()`,
+/// `:first-of-type`, `:last-of-type`, `:only-of-type`, `:nth-of-type()`,
+/// `:nth-last-of-type()`, `:not()`, `:has()`, `:root` and `:empty`
+/// pseudo-classes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    pub(crate) selectors: Vec<ComplexSelector>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Combinator {
+    Descendant,
+    Child,
+    NextSibling,
+    SubsequentSibling
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ComplexSelector {
+    pub(crate) compounds: Vec<CompoundSelector>,
+    /// `combinators[i]` joins `compounds[i]` and `compounds[i + 1]`.
+    pub(crate) combinators: Vec<Combinator>
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct CompoundSelector {
+    pub(crate) name: Option<String>,
+    pub(crate) id: Option<String>,
+    pub(crate) classes: Vec<String>,
+    pub(crate) attributes: Vec<AttributeSelector>,
+    pub(crate) pseudo_classes: Vec<PseudoClass>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AttributeOperator {
+    Exists,
+    Equals,
+    Includes,
+    DashMatch,
+    Prefix,
+    Suffix,
+    Substring
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AttributeSelector {
+    pub(crate) name: String,
+    pub(crate) operator: AttributeOperator,
+    pub(crate) value: String,
+    pub(crate) case_insensitive: bool
+}
+
+/// The `an+b` argument of the `:nth-*` pseudo-classes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Nth {
+    a: i64,
+    b: i64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PseudoClass {
+    NthChild { nth: Nth, of_type: bool, from_end: bool },
+    OnlyChild { of_type: bool },
+    Not(Selector),
+    Has(Vec<(Combinator, ComplexSelector)>),
+    Root,
+    Empty
+}
+
+impl Selector {
+    pub fn parse(selectors: &str) -> Result<Selector, SelectorError> {
+        let mut parser = SelectorParser { source: selectors, offset: 0 };
+        parser.skip_whitespace();
+        let selector = Selector { selectors: parser.parse_list()? };
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected character"));
+        }
+
+        return Ok(selector);
+    }
+
+    pub fn matches(&self, document: &Document, node: NodeId) -> bool {
+        return self.selectors
+            .iter()
+            .any(|selector| selector.matches(document, selector.compounds.len() - 1, node, None));
+    }
+
+    /// Whether the selector only uses type, universal, class, id and attribute
+    /// selectors joined by descendant or child combinators, which can be
+    /// matched against the ancestors of an element alone.
+    pub(crate) fn is_streamable(&self) -> bool {
+        return self.selectors.iter().all(|selector| {
+            selector.compounds.iter().all(|compound| compound.pseudo_classes.is_empty())
+                && selector.combinators.iter().all(|combinator| matches!(combinator, Combinator::Descendant | Combinator::Child))
+        });
+    }
+
+    /// Matches a streamable selector against `element` nested in `ancestors`,
+    /// outermost first.
+    pub(crate) fn matches_path(&self, element: &HtmlElement, ancestors: &[HtmlElement]) -> bool {
+        return self.selectors
+            .iter()
+            .any(|selector| selector.matches_path(selector.compounds.len() - 1, element, ancestors));
+    }
+
+    /// All elements below `scope` matching the selector, in document order.
+    pub fn select(&self, document: &Document, scope: NodeId) -> Vec<NodeId> {
+        return document
+            .descendants(scope)
+            .filter(|node| self.matches(document, *node))
+            .collect();
+    }
+}
+
+impl<'a> Document<'a> {
+    /// All elements of the document matching `selectors`, in document order.
+    pub fn select(&self, selectors: &str) -> Result<Vec<NodeId>, SelectorError> {
+        return Ok(Selector::parse(selectors)?.select(self, self.root()));
+    }
+
+    fn parent_element(&self, node: NodeId) -> Option<NodeId> {
+        let parent = self.node(node).parent()?;
+        self.node(parent).element()?;
+
+        return Some(parent);
+    }
+
+    fn previous_element_sibling(&self, node: NodeId) -> Option<NodeId> {
+        let mut sibling = self.node(node).previous_sibling();
+        while let Some(id) = sibling {
+            if self.node(id).element().is_some() {
+                return Some(id);
+            }
+
+            sibling = self.node(id).previous_sibling();
+        }
+
+        return None;
+    }
+}
+
+impl ComplexSelector {
+    /// Matches `compounds[0..=index]` right to left with `compounds[index]`
+    /// at `node`. A relative selector from `:has()` passes the element it is
+    /// relative to as `anchor`.
+    fn matches(&self, document: &Document, index: usize, node: NodeId, anchor: Option<(Combinator, NodeId)>) -> bool {
+        if !self.compounds[index].matches(document, node) {
+            return false;
+        }
+
+        if index == 0 {
+            return match anchor {
+                Some((combinator, anchor)) => is_related(document, combinator, anchor, node),
+                None => true
+            };
+        }
+
+        let matches_at = |node: NodeId| self.matches(document, index - 1, node, anchor);
+        return match self.combinators[index - 1] {
+            Combinator::Child => document.parent_element(node).is_some_and(matches_at),
+            Combinator::NextSibling => document.previous_element_sibling(node).is_some_and(matches_at),
+            Combinator::Descendant => {
+                let mut ancestor = document.parent_element(node);
+                while let Some(id) = ancestor {
+                    if matches_at(id) {
+                        return true;
+                    }
+
+                    ancestor = document.parent_element(id);
+                }
+
+                false
+            }
+
+            Combinator::SubsequentSibling => {
+                let mut sibling = document.previous_element_sibling(node);
+                while let Some(id) = sibling {
+                    if matches_at(id) {
+                        return true;
+                    }
+
+                    sibling = document.previous_element_sibling(id);
+                }
+
+                false
+            }
+        };
+    }
+
+    fn matches_path(&self, index: usize, element: &HtmlElement, ancestors: &[HtmlElement]) -> bool {
+        if !self.compounds[index].matches_element(element) {
+            return false;
+        }
+
+        if index == 0 {
+            return true;
+        }
+
+        return match self.combinators[index - 1] {
+            Combinator::Child => ancestors
+                .split_last()
+                .is_some_and(|(parent, ancestors)| self.matches_path(index - 1, parent, ancestors)),
+            _ => (0..ancestors.len())
+                .rev()
+                .any(|position| self.matches_path(index - 1, &ancestors[position], &ancestors[0..position]))
+        };
+    }
+}
+
+fn is_related(document: &Document, combinator: Combinator, anchor: NodeId, node: NodeId) -> bool {
+    return match combinator {
+        Combinator::Child => document.parent_element(node) == Some(anchor),
+        Combinator::NextSibling => document.previous_element_sibling(node) == Some(anchor),
+        Combinator::Descendant => {
+            let mut ancestor = document.parent_element(node);
+            while let Some(id) = ancestor {
+                if id == anchor {
+                    return true;
+                }
+
+                ancestor = document.parent_element(id);
+            }
+
+            false
+        }
+
+        Combinator::SubsequentSibling => {
+            let mut sibling = document.previous_element_sibling(node);
+            while let Some(id) = sibling {
+                if id == anchor {
+                    return true;
+                }
+
+                sibling = document.previous_element_sibling(id);
+            }
+
+            false
+        }
+    };
+}
+
+impl CompoundSelector {
+    fn matches(&self, document: &Document, node: NodeId) -> bool {
+        return document.node(node).element().is_some_and(|element| self.matches_element(element))
+            && self.pseudo_classes.iter().all(|pseudo_class| pseudo_class.matches(document, node));
+    }
+
+    /// Matches everything but the pseudo-classes.
+    fn matches_element(&self, element: &HtmlElement) -> bool {
+        // Selectors are lowercased, while SVG and MathML names keep their
+        // adjusted case, so those are compared case-insensitively.
+        let is_html = element.namespace == Namespace::Html;
+        if self.name.as_ref().is_some_and(|name| !matches_name(name, element.name.to_str(), is_html)) {
+            return false;
+        }
+
+        if self.id.as_ref().is_some_and(|id| element.attributes.get("id").is_none_or(|value| value != id)) {
+            return false;
+        }
+
+        let classes = element.attributes.get("class").map_or("", |value| value.as_ref());
+        if !self.classes.iter().all(|class| classes.split_whitespace().any(|value| value == class)) {
+            return false;
+        }
+
+        return self.attributes
+            .iter()
+            .all(|attribute| attribute_value(element, &attribute.name, is_html).is_some_and(|value| attribute.matches(value)));
+    }
+}
+
+fn attribute_value<'e>(element: &'e HtmlElement, name: &str, is_html: bool) -> Option<&'e str> {
+    if is_html {
+        return element.attributes.get(name).map(|value| value.as_ref());
+    }
+
+    return element.attributes
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_ref());
+}
+
+fn matches_name(expected: &str, name: &str, is_html: bool) -> bool {
+    if is_html {
+        return expected == name;
+    }
+
+    return expected.eq_ignore_ascii_case(name);
+}
+
+impl AttributeSelector {
+    fn matches(&self, value: &str) -> bool {
+        let (value, expected) = if self.case_insensitive {
+            (value.to_lowercase(), self.value.to_lowercase())
+        } else {
+            (value.to_string(), self.value.clone())
+        };
+
+        return match self.operator {
+            AttributeOperator::Exists => true,
+            AttributeOperator::Equals => value == expected,
+            AttributeOperator::Includes => value.split_whitespace().any(|word| word == expected),
+            AttributeOperator::DashMatch => value == expected || value.strip_prefix(expected.as_str()).is_some_and(|rest| rest.starts_with('-')),
+            AttributeOperator::Prefix => !expected.is_empty() && value.starts_with(expected.as_str()),
+            AttributeOperator::Suffix => !expected.is_empty() && value.ends_with(expected.as_str()),
+            AttributeOperator::Substring => !expected.is_empty() && value.contains(expected.as_str())
+        };
+    }
+}
+
+impl Nth {
+    fn matches(&self, index: usize) -> bool {
+        let offset = index as i64 - self.b;
+        if self.a == 0 {
+            return offset == 0;
+        }
+
+        return offset % self.a == 0 && offset / self.a >= 0;
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, document: &Document, node: NodeId) -> bool {
+        return match self {
+            PseudoClass::NthChild { nth, of_type, from_end } => nth.matches(sibling_index(document, node, *of_type, *from_end)),
+            PseudoClass::OnlyChild { of_type } => {
+                sibling_index(document, node, *of_type, false) == 1 && sibling_index(document, node, *of_type, true) == 1
+            }
+
+            PseudoClass::Not(selector) => !selector.matches(document, node),
+            PseudoClass::Has(selectors) => selectors.iter().any(|(combinator, selector)| {
+                let scope = match combinator {
+                    Combinator::Descendant | Combinator::Child => Some(node),
+                    Combinator::NextSibling | Combinator::SubsequentSibling => document.node(node).parent()
+                };
+
+                scope.is_some_and(|scope| document
+                    .descendants(scope)
+                    .any(|candidate| selector.matches(document, selector.compounds.len() - 1, candidate, Some((*combinator, node)))))
+            }),
+
+            PseudoClass::Root => document.node(node).parent() == Some(document.root()),
+            PseudoClass::Empty => document.children(node).all(|child| match &document.node(child).data {
+                NodeData::Element(_) => false,
+                NodeData::Text(text) => text.is_empty(),
+                _ => true
+            })
+        };
+    }
+}
+
+/// 1-based index of `node` among its element siblings, or among the siblings
+/// of the same type.
+fn sibling_index(document: &Document, node: NodeId, of_type: bool, from_end: bool) -> usize {
+    let Some(parent) = document.node(node).parent() else {
+        return 1;
+    };
+
+    let name = document.node(node).element().map(|element| element.name.to_str());
+    let siblings: Vec<NodeId> = document
+        .children(parent)
+        .filter(|id| match document.node(*id).element() {
+            Some(element) => !of_type || Some(element.name.to_str()) == name,
+            None => false
+        })
+        .collect();
+
+    let index = siblings.iter().position(|id| *id == node).unwrap_or(0);
+    if from_end {
+        return siblings.len() - index;
+    }
+
+    return index + 1;
+}
+
+struct SelectorParser<'s> {
+    source: &'s str,
+    offset: usize
+}
+
+impl SelectorParser<'_> {
+    fn error(&self, message: &'static str) -> SelectorError {
+        return SelectorError { message, offset: self.offset };
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.source[self.offset..].chars().next();
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.offset += char.len_utf8();
+
+        return Some(char);
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        if self.peek() != Some(expected) {
+            return Err(self.error(match expected {
+                ')' => "expected `)`",
+                ']' => "expected `]`",
+                '(' => "expected `(`",
+                _ => "unexpected character"
+            }));
+        }
+
+        self.bump();
+
+        return Ok(());
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.offset;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+
+        return self.offset > start;
+    }
+
+    /// Comma separated selectors, up to the end of the input or a `)`.
+    fn parse_list(&mut self) -> Result<Vec<ComplexSelector>, SelectorError> {
+        let mut selectors = vec![self.parse_complex()?];
+        while self.peek() == Some(',') {
+            self.bump();
+            self.skip_whitespace();
+            selectors.push(self.parse_complex()?);
+        }
+
+        return Ok(selectors);
+    }
+
+    fn parse_relative_list(&mut self) -> Result<Vec<(Combinator, ComplexSelector)>, SelectorError> {
+        let mut selectors = vec![];
+        loop {
+            let combinator = self.parse_combinator().unwrap_or(Combinator::Descendant);
+            selectors.push((combinator, self.parse_complex()?));
+            if self.peek() != Some(',') {
+                return Ok(selectors);
+            }
+
+            self.bump();
+            self.skip_whitespace();
+        }
+    }
+
+    /// Consumes an explicit `>`, `+` or `~` combinator and the whitespace
+    /// around it.
+    fn parse_combinator(&mut self) -> Option<Combinator> {
+        let combinator = match self.peek()? {
+            '>' => Combinator::Child,
+            '+' => Combinator::NextSibling,
+            '~' => Combinator::SubsequentSibling,
+            _ => return None
+        };
+
+        self.bump();
+        self.skip_whitespace();
+
+        return Some(combinator);
+    }
+
+    fn parse_complex(&mut self) -> Result<ComplexSelector, SelectorError> {
+        let mut selector = ComplexSelector { compounds: vec![self.parse_compound()?], combinators: vec![] };
+        loop {
+            let had_whitespace = self.skip_whitespace();
+            let combinator = match self.parse_combinator() {
+                Some(combinator) => combinator,
+                None if matches!(self.peek(), None | Some(',') | Some(')')) => break,
+                None if had_whitespace => Combinator::Descendant,
+                None => return Err(self.error("unexpected character"))
+            };
+
+            selector.combinators.push(combinator);
+            selector.compounds.push(self.parse_compound()?);
+        }
+
+        return Ok(selector);
+    }
+
+    fn parse_compound(&mut self) -> Result<CompoundSelector, SelectorError> {
+        let start = self.offset;
+        let mut compound = CompoundSelector::default();
+        if self.peek() == Some('*') {
+            self.bump();
+        } else if self.peek().is_some_and(is_identifier_start) {
+            compound.name = Some(self.parse_identifier()?.to_lowercase());
+        }
+
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.bump();
+                    compound.id = Some(self.parse_identifier()?);
+                }
+
+                Some('.') => {
+                    self.bump();
+                    compound.classes.push(self.parse_identifier()?);
+                }
+
+                Some('[') => {
+                    self.bump();
+                    compound.attributes.push(self.parse_attribute()?);
+                }
+
+                Some(':') => {
+                    self.bump();
+                    compound.pseudo_classes.push(self.parse_pseudo_class()?);
+                }
+
+                _ => break
+            }
+        }
+
+        if self.offset == start {
+            return Err(self.error("expected a selector"));
+        }
+
+        return Ok(compound);
+    }
+
+    fn parse_identifier(&mut self) -> Result<String, SelectorError> {
+        let mut identifier = String::new();
+        while let Some(char) = self.peek() {
+            if char == '\\' {
+                self.bump();
+                identifier.push(self.bump().ok_or_else(|| self.error("unexpected end of selector"))?);
+            } else if is_identifier_start(char) || char.is_ascii_digit() {
+                self.bump();
+                identifier.push(char);
+            } else {
+                break;
+            }
+        }
+
+        if identifier.is_empty() {
+            return Err(self.error("expected an identifier"));
+        }
+
+        return Ok(identifier);
+    }
+
+    fn parse_string(&mut self, quote: char) -> Result<String, SelectorError> {
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => value.push(self.bump().ok_or_else(|| self.error("unterminated string"))?),
+                Some(char) if char == quote => return Ok(value),
+                Some(char) => value.push(char),
+                None => return Err(self.error("unterminated string"))
+            }
+        }
+    }
+
+    fn parse_attribute(&mut self) -> Result<AttributeSelector, SelectorError> {
+        self.skip_whitespace();
+        let name = self.parse_identifier()?.to_lowercase();
+        self.skip_whitespace();
+
+        let operator = match self.peek() {
+            Some(']') => {
+                self.bump();
+
+                return Ok(AttributeSelector { name, operator: AttributeOperator::Exists, value: String::new(), case_insensitive: false });
+            }
+
+            Some('=') => AttributeOperator::Equals,
+            Some('~') => AttributeOperator::Includes,
+            Some('|') => AttributeOperator::DashMatch,
+            Some('^') => AttributeOperator::Prefix,
+            Some('$') => AttributeOperator::Suffix,
+            Some('*') => AttributeOperator::Substring,
+            _ => return Err(self.error("expected an attribute operator"))
+        };
+
+        if operator != AttributeOperator::Equals {
+            self.bump();
+        }
+
+        self.expect('=')?;
+        self.skip_whitespace();
+        let value = match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.parse_string(quote)?,
+            _ => self.parse_identifier()?
+        };
+
+        self.skip_whitespace();
+        let case_insensitive = matches!(self.peek(), Some('i' | 'I'));
+        if matches!(self.peek(), Some('i' | 'I' | 's' | 'S')) {
+            self.bump();
+            self.skip_whitespace();
+        }
+
+        self.expect(']')?;
+
+        return Ok(AttributeSelector { name, operator, value, case_insensitive });
+    }
+
+    fn parse_pseudo_class(&mut self) -> Result<PseudoClass, SelectorError> {
+        let start = self.offset;
+        let name = self.parse_identifier()?.to_lowercase();
+        let first = Nth { a: 0, b: 1 };
+        let pseudo_class = match name.as_str() {
+            "first-child" => PseudoClass::NthChild { nth: first, of_type: false, from_end: false },
+            "last-child" => PseudoClass::NthChild { nth: first, of_type: false, from_end: true },
+            "first-of-type" => PseudoClass::NthChild { nth: first, of_type: true, from_end: false },
+            "last-of-type" => PseudoClass::NthChild { nth: first, of_type: true, from_end: true },
+            "only-child" => PseudoClass::OnlyChild { of_type: false },
+            "only-of-type" => PseudoClass::OnlyChild { of_type: true },
+            "root" => PseudoClass::Root,
+            "empty" => PseudoClass::Empty,
+            "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" => {
+                self.expect('(')?;
+                let nth = self.parse_nth()?;
+
+                PseudoClass::NthChild { nth, of_type: name.ends_with("of-type"), from_end: name.contains("last") }
+            }
+
+            "not" => {
+                self.expect('(')?;
+                self.skip_whitespace();
+                let selector = Selector { selectors: self.parse_list()? };
+                self.expect(')')?;
+
+                PseudoClass::Not(selector)
+            }
+
+            "has" => {
+                self.expect('(')?;
+                self.skip_whitespace();
+                let selectors = self.parse_relative_list()?;
+                self.expect(')')?;
+
+                PseudoClass::Has(selectors)
+            }
+
+            _ => return Err(SelectorError { message: "unsupported pseudo-class", offset: start })
+        };
+
+        return Ok(pseudo_class);
+    }
+
+    /// Parses `odd`, `even`, `b`, `an` or `an+b` up to and including `)`.
+    fn parse_nth(&mut self) -> Result<Nth, SelectorError> {
+        let start = self.offset;
+        let length = self.source[start..].find(')').ok_or_else(|| self.error("expected `)`"))?;
+        let argument: String = self.source[start..start + length]
+            .chars()
+            .filter(|char| !char.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+
+        let invalid = SelectorError { message: "invalid nth argument", offset: start };
+        let parse_number = |value: &str| value.strip_prefix('+').unwrap_or(value).parse::<i64>().map_err(|_| invalid.clone());
+        let nth = match argument.as_str() {
+            "odd" => Nth { a: 2, b: 1 },
+            "even" => Nth { a: 2, b: 0 },
+            _ => match argument.split_once('n') {
+                Some((a, b)) => Nth {
+                    a: match a {
+                        "" | "+" => 1,
+                        "-" => -1,
+                        _ => parse_number(a)?
+                    },
+                    b: if b.is_empty() { 0 } else { parse_number(b)? }
+                },
+
+                None => Nth { a: 0, b: parse_number(&argument)? }
+            }
+        };
+
+        self.offset = start + length + 1;
+
+        return Ok(nth);
+    }
+}
+
+fn is_identifier_start(char: char) -> bool {
+    return char.is_alphabetic() || matches!(char, '-' | '_' | '\\') || !char.is_ascii();
+}
@@ -0,0 +1,50 @@
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use crate::element::{HtmlElement, HtmlElementName};
+
+/// Stack of open elements shared by the streaming parser and the tree
+/// builder, so both agree on which element a closing tag closes.
+#[derive(Debug)]
+pub(crate) struct ElementStack<T> {
+    elements: Vec<T>
+}
+
+impl<T: AsRef<HtmlElementName>> ElementStack<T> {
+    pub(crate) fn new() -> ElementStack<T> {
+        return ElementStack { elements: vec![] };
+    }
+
+    /// Pushes an opened element, void elements and the doctype never have
+    /// content and are not pushed.
+    pub(crate) fn push(&mut self, element: T) -> () {
+        let name = element.as_ref();
+        if name.is_self_closing_element() || name.is_element(HtmlElementName::Doctype) {
+            return;
+        }
+
+        self.elements.push(element);
+    }
+
+    /// Pops the nearest open element named `name` together with the elements
+    /// opened inside of it, closing tags matching no open element are ignored.
+    pub(crate) fn pop(&mut self, name: &HtmlElementName) -> Option<T> {
+        let index = self.elements.iter().rposition(|element| element.as_ref().to_str() == name.to_str())?;
+        self.elements.truncate(index + 1);
+
+        return self.elements.pop();
+    }
+
+    pub(crate) fn last(&self) -> Option<&T> {
+        return self.elements.last();
+    }
+
+    pub(crate) fn as_slice(&self) -> &[T] {
+        return self.elements.as_slice();
+    }
+}
+
+impl AsRef<HtmlElementName> for HtmlElement<'_> {
+    fn as_ref(&self) -> &HtmlElementName {
+        return &self.name;
+    }
+}
@@ -0,0 +1,1648 @@
+//! HTML tree construction on top of the tokenizer events.
+//!
+//! Events are final once emitted, which makes two parts of the WHATWG
+//! algorithm differ from the standard:
+//!
+//! - The adoption agency algorithm does not reparent the contents of a
+//!   furthest block. The elements above the formatting element are closed and
+//!   reopened instead, so `<b>1<p>2</b>3</p>` gives `<b>1<p>2</p></b><p>3</p>`
+//!   rather than `<b>1</b><p><b>2</b>3</p>`.
+//! - A `frameset` start tag after the body has been opened is ignored instead
+//!   of replacing the body.
+//!
+//! Both are tracked as known failures of the html5lib fixtures.
+
+#![allow(clippy::needless_return, clippy::unused_unit)]
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+
+use crate::doctype::{self, QuirksMode};
+use crate::element::{HtmlElement, HtmlElementName, Namespace};
+use crate::error::ParseError;
+use crate::position::{Position, Span};
+use crate::HtmlEvent;
+
+/// Elements closed by "generate implied end tags".
+const IMPLIED_END_TAGS: [&str; 10] = ["dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc"];
+
+/// Elements closed by "generate all implied end tags thoroughly".
+const IMPLIED_END_TAGS_THOROUGHLY: [&str; 18] = [
+    "caption", "colgroup", "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc", "tbody",
+    "td", "tfoot", "th", "thead", "tr"
+];
+
+/// Formatting elements tracked in the list of active formatting elements.
+const FORMATTING_ELEMENTS: [&str; 14] = ["a", "b", "big", "code", "em", "font", "i", "nobr", "s", "small", "strike", "strong", "tt", "u"];
+
+/// HTML elements of the "special" category.
+const SPECIAL_ELEMENTS: [&str; 83] = [
+    "address", "applet", "area", "article", "aside", "base", "basefont", "bgsound", "blockquote", "body",
+    "br", "button", "caption", "center", "col", "colgroup", "dd", "details", "dir", "div", "dl", "dt",
+    "embed", "fieldset", "figcaption", "figure", "footer", "form", "frame", "frameset", "h1", "h2", "h3",
+    "h4", "h5", "h6", "head", "header", "hgroup", "hr", "html", "iframe", "img", "input", "keygen", "li",
+    "link", "listing", "main", "marquee", "menu", "meta", "nav", "noembed", "noframes", "noscript",
+    "object", "ol", "p", "param", "plaintext", "pre", "script", "search", "section", "select", "source",
+    "style", "summary", "table", "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "title",
+    "tr", "track", "ul", "wbr", "xmp"
+];
+
+/// Start tags that close a `p` element in button scope before they open.
+const BLOCK_ELEMENTS: [&str; 25] = [
+    "address", "article", "aside", "blockquote", "center", "details", "dialog", "dir", "div", "dl",
+    "fieldset", "figcaption", "figure", "footer", "header", "hgroup", "main", "menu", "nav", "ol", "p",
+    "search", "section", "summary", "ul"
+];
+
+const HEADINGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// HTML start tags that break out of SVG and MathML content.
+const BREAKOUT_ELEMENTS: [&str; 44] = [
+    "b", "big", "blockquote", "body", "br", "center", "code", "dd", "div", "dl", "dt", "em", "embed",
+    "h1", "h2", "h3", "h4", "h5", "h6", "head", "hr", "i", "img", "li", "listing", "menu", "meta", "nobr",
+    "ol", "p", "pre", "ruby", "s", "small", "span", "strong", "strike", "sub", "sup", "table", "tt", "u",
+    "ul", "var"
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InsertionMode {
+    Initial,
+    BeforeHtml,
+    BeforeHead,
+    InHead,
+    AfterHead,
+    InBody,
+    Text,
+    InTable,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
+    InSelect,
+    InTemplate,
+    AfterBody,
+    InFrameset,
+    AfterFrameset
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+    Select
+}
+
+#[derive(Debug)]
+enum Token<'a> {
+    Doctype(HtmlEvent<'a>),
+    StartTag(HtmlElement<'a>),
+    EndTag(HtmlElement<'a>),
+    Text(Cow<'a, str>, Span),
+    Comment(Cow<'a, str>, Span),
+    Eof
+}
+
+/// Bookkeeping for an entry of the stack of open elements.
+#[derive(Debug)]
+struct OpenElement {
+    id: usize,
+    foreign: bool,
+    /// Index of the opening event in `held`, only kept up to date for tables.
+    opened_at: usize,
+    /// Index in `held` where children go, `None` appends them.
+    insert_at: Option<usize>
+}
+
+/// Entry of the list of active formatting elements, `None` entries in the
+/// list are markers.
+#[derive(Debug)]
+struct FormattingElement<'a> {
+    id: usize,
+    element: HtmlElement<'a>
+}
+
+/// Tree construction stage following the WHATWG insertion modes. Tokens go
+/// in, events describing a well-formed tree come out: elements left open are
+/// closed by synthetic `HtmlElementClosed` events, stray end tags are dropped
+/// and `html`, `head`, `body` and table sections are implied where missing.
+///
+/// Events from the opening of the outermost open table on are held back, so
+/// that content foster parented out of the table can be emitted in front of
+/// it.
+#[derive(Debug)]
+pub(crate) struct TreeBuilder<'a> {
+    mode: InsertionMode,
+    original_mode: InsertionMode,
+    /// Stack of template insertion modes, one entry per open `template`.
+    template_modes: Vec<InsertionMode>,
+    head_seen: bool,
+    quirks_mode: QuirksMode,
+    foster_parenting: bool,
+    /// Set after `pre`, `listing` and `textarea` start tags, a newline right
+    /// behind them is not part of the content.
+    skip_newline: bool,
+    elements: Vec<HtmlElement<'a>>,
+    open: Vec<OpenElement>,
+    formatting: Vec<Option<FormattingElement<'a>>>,
+    next_id: usize,
+    held: Vec<HtmlEvent<'a>>,
+    /// Start of the token being processed, synthetic events get an empty span
+    /// there.
+    position: Position
+}
+
+impl<'a> TreeBuilder<'a> {
+    pub(crate) fn new() -> TreeBuilder<'a> {
+        return TreeBuilder {
+            mode: InsertionMode::Initial,
+            original_mode: InsertionMode::Initial,
+            template_modes: vec![],
+            head_seen: false,
+            quirks_mode: QuirksMode::NoQuirks,
+            foster_parenting: false,
+            skip_newline: false,
+            elements: vec![],
+            open: vec![],
+            formatting: vec![],
+            next_id: 0,
+            held: vec![],
+            position: Position::new()
+        };
+    }
+
+    /// The stack of open elements, outermost first.
+    pub(crate) fn open_elements(&self) -> &[HtmlElement<'a>] {
+        return &self.elements;
+    }
+
+    /// Runs a token produced by the tokenizer through the tree construction
+    /// stage, the resulting events are pushed to `output`.
+    pub(crate) fn process(&mut self, event: HtmlEvent<'a>, output: &mut VecDeque<Result<HtmlEvent<'a>, ParseError>>) -> () {
+        let token = match event {
+            HtmlEvent::Doctype { span, .. } => {
+                self.position = span.start;
+                Token::Doctype(event)
+            }
+
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                self.position = opened_element.span.start;
+                Token::StartTag(opened_element)
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                self.position = closed_element.span.start;
+                Token::EndTag(closed_element)
+            }
+
+            HtmlEvent::TextContent { content, span, .. } => {
+                self.position = span.start;
+                Token::Text(content, span)
+            }
+
+            HtmlEvent::Comment { content, span, .. } => {
+                self.position = span.start;
+                Token::Comment(content, span)
+            }
+
+            HtmlEvent::HtmlDocumentEnd => Token::Eof
+        };
+
+        let skip_newline = std::mem::take(&mut self.skip_newline);
+        let token = match token {
+            Token::Text(content, span) if skip_newline && content.starts_with('\n') => {
+                let mut start = span.start;
+                start.advance("\n");
+                Token::Text(split_text(content, 1).1, Span::new(start, span.end))
+            }
+
+            token => token
+        };
+
+        if !matches!(&token, Token::Text(content, _) if content.is_empty()) {
+            self.process_token(token);
+        }
+
+        self.flush(output);
+    }
+
+    /// Implies whatever the document is missing and closes all elements left
+    /// open at `position`, the end of the document.
+    pub(crate) fn finish(&mut self, position: Position, output: &mut VecDeque<Result<HtmlEvent<'a>, ParseError>>) -> () {
+        self.position = position;
+        self.process_token(Token::Eof);
+        while !self.elements.is_empty() {
+            self.pop(None);
+        }
+
+        self.flush(output);
+    }
+
+    /// Emits the held events in front of the first place foster parenting
+    /// can still insert at, the opening event of the outermost open table.
+    fn flush(&mut self, output: &mut VecDeque<Result<HtmlEvent<'a>, ParseError>>) -> () {
+        let end = self.elements
+            .iter()
+            .zip(&self.open)
+            .filter_map(|(element, open)| match element.name.is_element(HtmlElementName::Table) {
+                true => Some(open.opened_at),
+                false => open.insert_at
+            })
+            .min()
+            .unwrap_or(self.held.len());
+
+        output.extend(self.held.drain(..end).map(Ok));
+        for open in self.open.iter_mut() {
+            open.opened_at = open.opened_at.saturating_sub(end);
+            if let Some(insert_at) = open.insert_at.as_mut() {
+                *insert_at -= end;
+            }
+        }
+    }
+
+    fn process_token(&mut self, token: Token<'a>) -> () {
+        if self.is_foreign_content(&token) {
+            self.process_foreign_content(token);
+
+            return;
+        }
+
+        self.process_in_mode(token);
+    }
+
+    /// Processes `token` by the rules of the current insertion mode, even
+    /// when the current node is foreign.
+    fn process_in_mode(&mut self, token: Token<'a>) -> () {
+        let token = match token {
+            Token::Text(content, span) if matches!(
+                self.mode,
+                InsertionMode::Initial | InsertionMode::BeforeHtml | InsertionMode::BeforeHead | InsertionMode::InHead
+                    | InsertionMode::AfterHead | InsertionMode::InColumnGroup | InsertionMode::AfterBody
+            ) => {
+                match self.process_leading_whitespace(content, span) {
+                    Some(token) => token,
+                    None => return
+                }
+            }
+
+            token => token
+        };
+
+        match self.mode {
+            InsertionMode::Initial => self.process_initial(token),
+            InsertionMode::BeforeHtml => self.process_before_html(token),
+            InsertionMode::BeforeHead => self.process_before_head(token),
+            InsertionMode::InHead => self.process_in_head(token),
+            InsertionMode::AfterHead => self.process_after_head(token),
+            InsertionMode::InBody => self.process_in_body(token),
+            InsertionMode::Text => self.process_text(token),
+            InsertionMode::InTable => self.process_in_table(token),
+            InsertionMode::InCaption => self.process_in_caption(token),
+            InsertionMode::InColumnGroup => self.process_in_column_group(token),
+            InsertionMode::InTableBody => self.process_in_table_body(token),
+            InsertionMode::InRow => self.process_in_row(token),
+            InsertionMode::InCell => self.process_in_cell(token),
+            InsertionMode::InSelect => self.process_in_select(token),
+            InsertionMode::InTemplate => self.process_in_template(token),
+            InsertionMode::AfterBody => self.process_after_body(token),
+            InsertionMode::InFrameset => self.process_in_frameset(token),
+            InsertionMode::AfterFrameset => self.process_after_frameset(token)
+        }
+    }
+
+    /// Handles the whitespace at the start of text in the modes that treat it
+    /// apart from other characters, returns the text left behind it.
+    fn process_leading_whitespace(&mut self, content: Cow<'a, str>, span: Span) -> Option<Token<'a>> {
+        let length = content.len() - content.trim_start_matches(is_whitespace).len();
+        if length == 0 {
+            return Some(Token::Text(content, span));
+        }
+
+        let (whitespace, rest) = split_text(content, length);
+        let mut middle = span.start;
+        middle.advance_str(&whitespace);
+        match self.mode {
+            InsertionMode::Initial | InsertionMode::BeforeHtml | InsertionMode::BeforeHead => {}
+            InsertionMode::AfterBody => self.process_in_body(Token::Text(whitespace, Span::new(span.start, middle))),
+            _ => self.insert_text(whitespace, Span::new(span.start, middle))
+        }
+
+        if rest.is_empty() {
+            return None;
+        }
+
+        return Some(Token::Text(rest, Span::new(middle, span.end)));
+    }
+
+    fn process_initial(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(doctype) => {
+                if let HtmlEvent::Doctype { name, public_id, system_id, force_quirks, .. } = &doctype {
+                    self.quirks_mode = doctype::quirks_mode(name.as_deref(), public_id.as_deref(), system_id.as_deref(), *force_quirks);
+                }
+
+                self.place(doctype, None);
+                self.mode = InsertionMode::BeforeHtml;
+            }
+
+            token => {
+                self.quirks_mode = QuirksMode::Quirks;
+                self.mode = InsertionMode::BeforeHtml;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn process_before_html(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &["html"]) => {
+                self.insert_element(element);
+                self.mode = InsertionMode::BeforeHead;
+            }
+
+            Token::EndTag(element) if !is(&element, &["head", "body", "html", "br"]) => {}
+            token => {
+                self.insert_synthetic("html");
+                self.mode = InsertionMode::BeforeHead;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn process_before_head(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &["html"]) => {}
+            Token::StartTag(element) if is(&element, &["head"]) => {
+                self.insert_element(element);
+                self.head_seen = true;
+                self.mode = InsertionMode::InHead;
+            }
+
+            Token::EndTag(element) if !is(&element, &["head", "body", "html", "br"]) => {}
+            token => {
+                self.insert_synthetic("head");
+                self.head_seen = true;
+                self.mode = InsertionMode::InHead;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn process_in_head(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &["html", "head"]) => {}
+            Token::StartTag(element) if is(&element, &["base", "basefont", "bgsound", "link", "meta"]) => {
+                self.insert_empty(element);
+            }
+
+            Token::StartTag(element) if is(&element, &["title", "noframes", "style", "script"]) => {
+                self.insert_element(element);
+                self.original_mode = self.mode;
+                self.mode = InsertionMode::Text;
+            }
+
+            Token::StartTag(element) if is(&element, &["noscript"]) => {
+                self.insert_element(element);
+            }
+
+            Token::EndTag(element) if is(&element, &["noscript"]) => {
+                if let Some(index) = self.find_in_stack("noscript") {
+                    self.generate_implied_end_tags(None);
+                    self.pop_to(index, Some(element));
+                }
+            }
+
+            Token::StartTag(element) if is(&element, &["template"]) => {
+                self.insert_element(element);
+                self.formatting.push(None);
+                self.mode = InsertionMode::InTemplate;
+                self.template_modes.push(InsertionMode::InTemplate);
+            }
+
+            Token::EndTag(element) if is(&element, &["template"]) => {
+                if let Some(index) = self.find_in_stack("template") {
+                    while self.current_is(&IMPLIED_END_TAGS_THOROUGHLY) {
+                        self.pop(None);
+                    }
+
+                    self.pop_to(index, Some(element));
+                    self.clear_formatting_to_marker();
+                    self.template_modes.pop();
+                    self.reset_insertion_mode();
+                }
+            }
+
+            Token::EndTag(element) if self.current_is(&["head"]) && is(&element, &["head"]) => {
+                self.pop(Some(element));
+                self.mode = InsertionMode::AfterHead;
+            }
+
+            Token::EndTag(element) if !is(&element, &["body", "html", "br"]) => {}
+            token => {
+                if self.current_is(&["head"]) {
+                    self.pop(None);
+                }
+
+                self.mode = InsertionMode::AfterHead;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn process_after_head(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &["html", "head"]) => {}
+            Token::StartTag(element) if is(&element, &["body"]) => {
+                self.insert_element(element);
+                self.mode = InsertionMode::InBody;
+            }
+
+            Token::StartTag(element) if is(&element, &["frameset"]) => {
+                self.insert_element(element);
+                self.mode = InsertionMode::InFrameset;
+            }
+
+            // The head is closed already, elements belonging into it are
+            // inserted where they are found.
+            Token::StartTag(element) if is(&element, &["base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title"]) => {
+                self.process_in_head(Token::StartTag(element));
+            }
+
+            Token::EndTag(element) if is(&element, &["template"]) => self.process_in_head(Token::EndTag(element)),
+            Token::EndTag(element) if !is(&element, &["body", "html", "br"]) => {}
+            token => {
+                self.insert_synthetic("body");
+                self.mode = InsertionMode::InBody;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn process_in_body(&mut self, token: Token<'a>) -> () {
+        let element = match token {
+            Token::Text(content, span) => {
+                self.reconstruct_formatting_elements();
+                self.insert_text(content, span);
+
+                return;
+            }
+
+            Token::Comment(content, span) => return self.insert_comment(content, span),
+            Token::Eof if !self.template_modes.is_empty() => return self.process_in_template(Token::Eof),
+            Token::Doctype(_) | Token::Eof => return,
+            Token::EndTag(element) => return self.process_end_tag_in_body(element),
+            Token::StartTag(element) => element
+        };
+
+        // The body is emitted already, so a `frameset` cannot replace it.
+        let name = element.name.to_str().to_string();
+        match name.as_str() {
+            "html" | "body" | "frameset" | "caption" | "col" | "colgroup" | "frame" | "head" | "tbody" | "td" | "tfoot" | "th" | "thead" | "tr" => {}
+            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes" | "script" | "style" | "template" | "title" => {
+                self.process_in_head(Token::StartTag(element));
+            }
+
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.close_p_in_button_scope();
+                if self.current_is(&HEADINGS) {
+                    self.pop(None);
+                }
+
+                self.insert_element(element);
+            }
+
+            "pre" | "listing" | "form" | "plaintext" => {
+                self.close_p_in_button_scope();
+                self.insert_element(element);
+                self.skip_newline = name == "pre" || name == "listing";
+            }
+
+            "li" | "dd" | "dt" => {
+                let closes: &[&str] = if name == "li" { &["li"] } else { &["dd", "dt"] };
+                for index in (0..self.elements.len()).rev() {
+                    if self.is_at(index, closes) {
+                        let closed = self.elements[index].name.to_str().to_string();
+                        self.close_element(&closed, None);
+                        break;
+                    }
+
+                    if self.is_special(index) && !self.is_at(index, &["address", "div", "p"]) {
+                        break;
+                    }
+                }
+
+                self.close_p_in_button_scope();
+                self.insert_element(element);
+            }
+
+            "button" => {
+                if self.in_scope(&["button"], Scope::Default).is_some() {
+                    self.close_element("button", None);
+                }
+
+                self.reconstruct_formatting_elements();
+                self.insert_element(element);
+            }
+
+            "a" => {
+                let open_anchor = self.formatting_after_marker()
+                    .rev()
+                    .find(|index| self.formatting[*index].as_ref().is_some_and(|entry| entry.element.name.to_str() == "a"));
+
+                if let Some(index) = open_anchor {
+                    let id = self.formatting[index].as_ref().unwrap().id;
+                    self.adoption_agency(synthetic_element("a", self.position));
+                    self.formatting.retain(|entry| entry.as_ref().is_none_or(|entry| entry.id != id));
+                    if let Some(index) = self.open.iter().position(|open| open.id == id) {
+                        self.pop_to(index, None);
+                    }
+                }
+
+                self.reconstruct_formatting_elements();
+                let id = self.insert_element(element.clone());
+                self.push_formatting_element(id, element);
+            }
+
+            "nobr" => {
+                self.reconstruct_formatting_elements();
+                if self.in_scope(&["nobr"], Scope::Default).is_some() {
+                    self.adoption_agency(synthetic_element("nobr", self.position));
+                    self.reconstruct_formatting_elements();
+                }
+
+                let id = self.insert_element(element.clone());
+                self.push_formatting_element(id, element);
+            }
+
+            _ if FORMATTING_ELEMENTS.contains(&name.as_str()) => {
+                self.reconstruct_formatting_elements();
+                let id = self.insert_element(element.clone());
+                self.push_formatting_element(id, element);
+            }
+
+            "applet" | "marquee" | "object" => {
+                self.reconstruct_formatting_elements();
+                self.insert_element(element);
+                self.formatting.push(None);
+            }
+
+            "table" => {
+                if self.quirks_mode != QuirksMode::Quirks {
+                    self.close_p_in_button_scope();
+                }
+
+                self.insert_element(element);
+                self.mode = InsertionMode::InTable;
+            }
+
+            "area" | "br" | "embed" | "img" | "keygen" | "wbr" | "input" => {
+                self.reconstruct_formatting_elements();
+                self.insert_empty(element);
+            }
+
+            "param" | "source" | "track" => self.insert_empty(element),
+            "hr" => {
+                self.close_p_in_button_scope();
+                self.insert_empty(element);
+            }
+
+            "image" => {
+                let mut element = element;
+                element.name = HtmlElementName::Img;
+                self.process_in_body(Token::StartTag(element));
+            }
+
+            "textarea" | "iframe" | "noembed" | "xmp" => {
+                if name == "xmp" {
+                    self.close_p_in_button_scope();
+                    self.reconstruct_formatting_elements();
+                }
+
+                self.insert_element(element);
+                self.skip_newline = name == "textarea";
+                self.original_mode = self.mode;
+                self.mode = InsertionMode::Text;
+            }
+
+            "select" => {
+                self.reconstruct_formatting_elements();
+                self.insert_element(element);
+                self.mode = InsertionMode::InSelect;
+            }
+
+            "optgroup" | "option" => {
+                if self.current_is(&["option"]) {
+                    self.pop(None);
+                }
+
+                self.reconstruct_formatting_elements();
+                self.insert_element(element);
+            }
+
+            "rb" | "rtc" | "rp" | "rt" => {
+                if self.in_scope(&["ruby"], Scope::Default).is_some() {
+                    let except = if name == "rp" || name == "rt" { Some("rtc") } else { None };
+                    self.generate_implied_end_tags(except);
+                }
+
+                self.insert_element(element);
+            }
+
+            _ if BLOCK_ELEMENTS.contains(&name.as_str()) => {
+                self.close_p_in_button_scope();
+                self.insert_element(element);
+            }
+
+            _ => {
+                self.reconstruct_formatting_elements();
+                self.insert_element(element);
+            }
+        }
+    }
+
+    fn process_end_tag_in_body(&mut self, element: HtmlElement<'a>) -> () {
+        let name = element.name.to_str().to_string();
+        match name.as_str() {
+            "body" | "html" => {
+                if self.in_scope(&["body"], Scope::Default).is_some() {
+                    self.mode = InsertionMode::AfterBody;
+                }
+            }
+
+            "template" => self.process_in_head(Token::EndTag(element)),
+            "p" => {
+                if self.in_scope(&["p"], Scope::Button).is_none() {
+                    self.insert_synthetic("p");
+                }
+
+                self.close_element("p", Some(element));
+            }
+
+            "li" | "dd" | "dt" => {
+                let scope = if name == "li" { Scope::ListItem } else { Scope::Default };
+                if self.in_scope(&[name.as_str()], scope).is_some() {
+                    self.close_element(&name, Some(element));
+                }
+            }
+
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                if let Some(index) = self.in_scope(&HEADINGS, Scope::Default) {
+                    self.generate_implied_end_tags(None);
+                    self.pop_to(index, Some(element));
+                }
+            }
+
+            "applet" | "marquee" | "object" => {
+                if self.in_scope(&[name.as_str()], Scope::Default).is_some() {
+                    self.close_element(&name, Some(element));
+                    self.clear_formatting_to_marker();
+                }
+            }
+
+            "br" => {
+                let mut element = element;
+                element.attributes = HashMap::new();
+                element.attribute_spans = HashMap::new();
+                self.process_in_body(Token::StartTag(element));
+            }
+
+            _ if FORMATTING_ELEMENTS.contains(&name.as_str()) => self.adoption_agency(element),
+            _ if BLOCK_ELEMENTS.contains(&name.as_str()) || matches!(name.as_str(), "button" | "form" | "listing" | "pre" | "dialog") => {
+                if self.in_scope(&[name.as_str()], Scope::Default).is_some() {
+                    self.close_element(&name, Some(element));
+                }
+            }
+
+            _ => self.any_other_end_tag(element)
+        }
+    }
+
+    fn process_text(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Text(content, span) => self.insert_text(content, span),
+            Token::EndTag(element) if self.current_is(&[element.name.to_str()]) => {
+                self.pop(Some(element));
+                self.mode = self.original_mode;
+            }
+
+            Token::Eof => {
+                self.pop(None);
+                self.mode = self.original_mode;
+                self.process_token(Token::Eof);
+            }
+
+            _ => {}
+        }
+    }
+
+    fn process_in_table(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Text(content, span) if self.current_is(&["table", "tbody", "tfoot", "thead", "tr"]) && content.chars().all(is_whitespace) => {
+                self.insert_text(content, span);
+            }
+
+            Token::Text(..) if self.current_is(&["table", "tbody", "tfoot", "thead", "tr"]) => self.foster_parent(token),
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &["caption"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.formatting.push(None);
+                self.insert_element(element);
+                self.mode = InsertionMode::InCaption;
+            }
+
+            Token::StartTag(element) if is(&element, &["colgroup"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_element(element);
+                self.mode = InsertionMode::InColumnGroup;
+            }
+
+            Token::StartTag(element) if is(&element, &["col"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_synthetic("colgroup");
+                self.mode = InsertionMode::InColumnGroup;
+                self.process_token(Token::StartTag(element));
+            }
+
+            Token::StartTag(element) if is(&element, &["tbody", "tfoot", "thead"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_element(element);
+                self.mode = InsertionMode::InTableBody;
+            }
+
+            Token::StartTag(element) if is(&element, &["td", "th", "tr"]) => {
+                self.clear_stack_back_to(&["table", "template", "html"]);
+                self.insert_synthetic("tbody");
+                self.mode = InsertionMode::InTableBody;
+                self.process_token(Token::StartTag(element));
+            }
+
+            Token::StartTag(element) if is(&element, &["table"]) => {
+                if let Some(index) = self.in_scope(&["table"], Scope::Table) {
+                    self.pop_to(index, None);
+                    self.reset_insertion_mode();
+                    self.process_token(Token::StartTag(element));
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["table"]) => {
+                if let Some(index) = self.in_scope(&["table"], Scope::Table) {
+                    self.pop_to(index, Some(element));
+                    self.reset_insertion_mode();
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["body", "caption", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr"]) => {}
+            Token::StartTag(element) if is(&element, &["style", "script", "template"]) => self.process_in_head(Token::StartTag(element)),
+            Token::EndTag(element) if is(&element, &["template"]) => self.process_in_head(Token::EndTag(element)),
+            Token::StartTag(element) if is(&element, &["input"]) && element.attributes.get("type").is_some_and(|value| value.eq_ignore_ascii_case("hidden")) => {
+                self.insert_empty(element);
+            }
+
+            Token::Eof => self.process_in_body(Token::Eof),
+            token => self.foster_parent(token)
+        }
+    }
+
+    fn foster_parent(&mut self, token: Token<'a>) -> () {
+        self.foster_parenting = true;
+        self.process_in_body(token);
+        self.foster_parenting = false;
+    }
+
+    fn process_in_caption(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::EndTag(element) if is(&element, &["caption"]) => {
+                self.close_caption(Some(element));
+            }
+
+            Token::StartTag(ref element) if is(element, &["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"]) => {
+                if self.close_caption(None) {
+                    self.process_token(token);
+                }
+            }
+
+            Token::EndTag(ref element) if is(element, &["table"]) => {
+                if self.close_caption(None) {
+                    self.process_token(token);
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["body", "col", "colgroup", "html", "tbody", "td", "tfoot", "th", "thead", "tr"]) => {}
+            token => self.process_in_body(token)
+        }
+    }
+
+    fn close_caption(&mut self, end_tag: Option<HtmlElement<'a>>) -> bool {
+        if self.in_scope(&["caption"], Scope::Table).is_none() {
+            return false;
+        }
+
+        self.close_element("caption", end_tag);
+        self.clear_formatting_to_marker();
+        self.mode = InsertionMode::InTable;
+
+        return true;
+    }
+
+    fn process_in_column_group(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &["html"]) => {}
+            Token::StartTag(element) if is(&element, &["col"]) => self.insert_empty(element),
+            Token::EndTag(element) if is(&element, &["colgroup"]) => {
+                if self.current_is(&["colgroup"]) {
+                    self.pop(Some(element));
+                    self.mode = InsertionMode::InTable;
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["col"]) => {}
+            Token::StartTag(element) if is(&element, &["template"]) => self.process_in_head(Token::StartTag(element)),
+            Token::EndTag(element) if is(&element, &["template"]) => self.process_in_head(Token::EndTag(element)),
+            Token::Eof => self.process_in_body(Token::Eof),
+            token => {
+                if self.current_is(&["colgroup"]) {
+                    self.pop(None);
+                    self.mode = InsertionMode::InTable;
+                    self.process_token(token);
+                }
+            }
+        }
+    }
+
+    fn process_in_table_body(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::StartTag(element) if is(&element, &["tr"]) => {
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_element(element);
+                self.mode = InsertionMode::InRow;
+            }
+
+            Token::StartTag(element) if is(&element, &["th", "td"]) => {
+                self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                self.insert_synthetic("tr");
+                self.mode = InsertionMode::InRow;
+                self.process_token(Token::StartTag(element));
+            }
+
+            Token::EndTag(element) if is(&element, &["tbody", "tfoot", "thead"]) => {
+                if self.in_scope(&[element.name.to_str()], Scope::Table).is_some() {
+                    self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+                    self.pop(Some(element));
+                    self.mode = InsertionMode::InTable;
+                }
+            }
+
+            Token::StartTag(ref element) if is(element, &["caption", "col", "colgroup", "tbody", "tfoot", "thead"]) => {
+                self.close_table_body(token);
+            }
+
+            Token::EndTag(ref element) if is(element, &["table"]) => self.close_table_body(token),
+            Token::EndTag(element) if is(&element, &["body", "caption", "col", "colgroup", "html", "td", "th", "tr"]) => {}
+            token => self.process_in_table(token)
+        }
+    }
+
+    fn close_table_body(&mut self, token: Token<'a>) -> () {
+        if self.in_scope(&["tbody", "thead", "tfoot"], Scope::Table).is_none() {
+            return;
+        }
+
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead", "template", "html"]);
+        self.pop(None);
+        self.mode = InsertionMode::InTable;
+        self.process_token(token);
+    }
+
+    fn process_in_row(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::StartTag(element) if is(&element, &["th", "td"]) => {
+                self.clear_stack_back_to(&["tr", "template", "html"]);
+                self.insert_element(element);
+                self.mode = InsertionMode::InCell;
+                self.formatting.push(None);
+            }
+
+            Token::EndTag(element) if is(&element, &["tr"]) => {
+                if self.in_scope(&["tr"], Scope::Table).is_some() {
+                    self.clear_stack_back_to(&["tr", "template", "html"]);
+                    self.pop(Some(element));
+                    self.mode = InsertionMode::InTableBody;
+                }
+            }
+
+            Token::StartTag(ref element) if is(element, &["caption", "col", "colgroup", "tbody", "tfoot", "thead", "tr"]) => {
+                self.close_row(token);
+            }
+
+            Token::EndTag(ref element) if is(element, &["table"]) => self.close_row(token),
+            Token::EndTag(ref element) if is(element, &["tbody", "tfoot", "thead"]) => {
+                if self.in_scope(&[element.name.to_str()], Scope::Table).is_some() {
+                    self.close_row(token);
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["body", "caption", "col", "colgroup", "html", "td", "th"]) => {}
+            token => self.process_in_table(token)
+        }
+    }
+
+    fn close_row(&mut self, token: Token<'a>) -> () {
+        if self.in_scope(&["tr"], Scope::Table).is_none() {
+            return;
+        }
+
+        self.clear_stack_back_to(&["tr", "template", "html"]);
+        self.pop(None);
+        self.mode = InsertionMode::InTableBody;
+        self.process_token(token);
+    }
+
+    fn process_in_cell(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::EndTag(element) if is(&element, &["td", "th"]) => {
+                if self.in_scope(&[element.name.to_str()], Scope::Table).is_some() {
+                    let name = element.name.to_str().to_string();
+                    self.close_element(&name, Some(element));
+                    self.clear_formatting_to_marker();
+                    self.mode = InsertionMode::InRow;
+                }
+            }
+
+            Token::StartTag(ref element) if is(element, &["caption", "col", "colgroup", "tbody", "td", "tfoot", "th", "thead", "tr"]) => {
+                if self.in_scope(&["td", "th"], Scope::Table).is_some() {
+                    self.close_cell();
+                    self.process_token(token);
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["body", "caption", "col", "colgroup", "html"]) => {}
+            Token::EndTag(ref element) if is(element, &["table", "tbody", "tfoot", "thead", "tr"]) => {
+                if self.in_scope(&[element.name.to_str()], Scope::Table).is_some() {
+                    self.close_cell();
+                    self.process_token(token);
+                }
+            }
+
+            token => self.process_in_body(token)
+        }
+    }
+
+    fn close_cell(&mut self) -> () {
+        self.generate_implied_end_tags(None);
+        if let Some(index) = self.in_scope(&["td", "th"], Scope::Table) {
+            self.pop_to(index, None);
+        }
+
+        self.clear_formatting_to_marker();
+        self.mode = InsertionMode::InRow;
+    }
+
+    fn process_in_select(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Text(content, span) => self.insert_text(content, span),
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::StartTag(element) if is(&element, &["option"]) => {
+                if self.current_is(&["option"]) {
+                    self.pop(None);
+                }
+
+                self.insert_element(element);
+            }
+
+            Token::StartTag(element) if is(&element, &["optgroup"]) => {
+                if self.current_is(&["option"]) {
+                    self.pop(None);
+                }
+
+                if self.current_is(&["optgroup"]) {
+                    self.pop(None);
+                }
+
+                self.insert_element(element);
+            }
+
+            Token::EndTag(element) if is(&element, &["optgroup"]) => {
+                let length = self.elements.len();
+                if self.current_is(&["option"]) && length > 1 && self.is_at(length - 2, &["optgroup"]) {
+                    self.pop(None);
+                }
+
+                if self.current_is(&["optgroup"]) {
+                    self.pop(Some(element));
+                }
+            }
+
+            Token::EndTag(element) if is(&element, &["option"]) && self.current_is(&["option"]) => self.pop(Some(element)),
+
+            Token::EndTag(element) if is(&element, &["select"]) => {
+                if let Some(index) = self.in_scope(&["select"], Scope::Select) {
+                    self.pop_to(index, Some(element));
+                    self.reset_insertion_mode();
+                }
+            }
+
+            Token::StartTag(element) if is(&element, &["select"]) => {
+                if let Some(index) = self.in_scope(&["select"], Scope::Select) {
+                    self.pop_to(index, None);
+                    self.reset_insertion_mode();
+                }
+            }
+
+            Token::StartTag(ref element) if is(element, &["input", "keygen", "textarea", "caption", "table", "tbody", "tfoot", "thead", "tr", "td", "th"]) => {
+                if let Some(index) = self.in_scope(&["select"], Scope::Select) {
+                    self.pop_to(index, None);
+                    self.reset_insertion_mode();
+                    self.process_token(token);
+                }
+            }
+
+            Token::StartTag(element) if is(&element, &["script", "template"]) => self.process_in_head(Token::StartTag(element)),
+            Token::EndTag(element) if is(&element, &["template"]) => self.process_in_head(Token::EndTag(element)),
+            Token::Eof => self.process_in_body(Token::Eof),
+            _ => {}
+        }
+    }
+
+    fn process_in_template(&mut self, token: Token<'a>) -> () {
+        let mode = match &token {
+            Token::Text(..) | Token::Comment(..) | Token::Doctype(_) => return self.process_in_body(token),
+            Token::StartTag(element) if is(element, &["base", "basefont", "bgsound", "link", "meta", "noframes", "script", "style", "template", "title"]) => {
+                return self.process_in_head(token);
+            }
+
+            Token::EndTag(element) if is(element, &["template"]) => return self.process_in_head(token),
+            Token::StartTag(element) if is(element, &["caption", "colgroup", "tbody", "tfoot", "thead"]) => InsertionMode::InTable,
+            Token::StartTag(element) if is(element, &["col"]) => InsertionMode::InColumnGroup,
+            Token::StartTag(element) if is(element, &["tr"]) => InsertionMode::InTableBody,
+            Token::StartTag(element) if is(element, &["td", "th"]) => InsertionMode::InRow,
+            Token::StartTag(_) => InsertionMode::InBody,
+            Token::EndTag(_) => return,
+            Token::Eof => {
+                let Some(index) = self.find_in_stack("template") else {
+                    return;
+                };
+
+                self.pop_to(index, None);
+                self.clear_formatting_to_marker();
+                self.template_modes.pop();
+                self.reset_insertion_mode();
+                self.process_token(token);
+
+                return;
+            }
+        };
+
+        self.template_modes.pop();
+        self.template_modes.push(mode);
+        self.mode = mode;
+        self.process_token(token);
+    }
+
+    fn process_after_body(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) | Token::Eof => {}
+            Token::EndTag(element) if is(&element, &["html"]) => {}
+            token => {
+                self.mode = InsertionMode::InBody;
+                self.process_token(token);
+            }
+        }
+    }
+
+    fn process_in_frameset(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Text(content, span) => self.insert_whitespace(content, span),
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::StartTag(element) if is(&element, &["frameset"]) => {
+                self.insert_element(element);
+            }
+
+            Token::EndTag(element) if is(&element, &["frameset"]) => {
+                if self.current_is(&["frameset"]) {
+                    self.pop(Some(element));
+                }
+
+                if !self.current_is(&["frameset"]) {
+                    self.mode = InsertionMode::AfterFrameset;
+                }
+            }
+
+            Token::StartTag(element) if is(&element, &["frame"]) => self.insert_empty(element),
+            Token::StartTag(element) if is(&element, &["noframes"]) => self.process_in_head(Token::StartTag(element)),
+            _ => {}
+        }
+    }
+
+    fn process_after_frameset(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Text(content, span) => self.insert_whitespace(content, span),
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::StartTag(element) if is(&element, &["noframes"]) => self.process_in_head(Token::StartTag(element)),
+            _ => {}
+        }
+    }
+
+    fn is_foreign_content(&self, token: &Token<'a>) -> bool {
+        let Some(current) = self.open.last() else {
+            return false;
+        };
+
+        if !current.foreign {
+            return false;
+        }
+
+        let index = self.elements.len() - 1;
+        return match token {
+            Token::StartTag(element) if self.is_at(index, &["mi", "mo", "mn", "ms", "mtext"]) => is(element, &["mglyph", "malignmark"]),
+            Token::StartTag(element) if self.is_at(index, &["annotation-xml"]) => !is(element, &["svg"]),
+            Token::StartTag(_) | Token::Text(..) => !self.is_integration_point(index),
+            Token::Eof => false,
+            _ => true
+        };
+    }
+
+    fn process_foreign_content(&mut self, token: Token<'a>) -> () {
+        match token {
+            Token::Text(content, span) => self.insert_text(content, span),
+            Token::Comment(content, span) => self.insert_comment(content, span),
+            Token::Doctype(_) => {}
+            Token::StartTag(element) if is(&element, &BREAKOUT_ELEMENTS)
+                || (is(&element, &["font"]) && ["color", "face", "size"].iter().any(|name| element.attributes.contains_key(*name))) =>
+            {
+                while self.open.last().is_some_and(|open| open.foreign) && !self.is_integration_point(self.elements.len() - 1) {
+                    self.pop(None);
+                }
+
+                self.process_token(Token::StartTag(element));
+            }
+
+            Token::StartTag(element) => {
+                let self_closing = element.self_closing;
+                self.insert_element(element);
+                if self_closing {
+                    self.pop_self_closing();
+                }
+            }
+
+            Token::EndTag(element) => {
+                for index in (0..self.elements.len()).rev() {
+                    if !self.open[index].foreign {
+                        self.process_in_mode(Token::EndTag(element));
+
+                        return;
+                    }
+
+                    if self.elements[index].name.to_str().eq_ignore_ascii_case(element.name.to_str()) {
+                        self.pop_to(index, Some(element));
+
+                        return;
+                    }
+                }
+            }
+
+            Token::Eof => self.process_in_mode(Token::Eof)
+        }
+    }
+
+    fn is_integration_point(&self, index: usize) -> bool {
+        return self.open[index].foreign && self.is_at(index, &["mi", "mo", "mn", "ms", "mtext", "annotation-xml", "foreignObject", "desc", "title"]);
+    }
+
+    fn is_at(&self, index: usize, names: &[&str]) -> bool {
+        return names.contains(&self.elements[index].name.to_str());
+    }
+
+    fn current_is(&self, names: &[&str]) -> bool {
+        return self.elements.last().is_some_and(|element| names.contains(&element.name.to_str()));
+    }
+
+    fn is_special(&self, index: usize) -> bool {
+        if self.open[index].foreign {
+            return self.is_integration_point(index);
+        }
+
+        return self.is_at(index, &SPECIAL_ELEMENTS);
+    }
+
+    fn find_in_stack(&self, name: &str) -> Option<usize> {
+        return self.elements.iter().rposition(|element| element.name.to_str() == name);
+    }
+
+    fn is_scope_boundary(&self, index: usize, scope: Scope) -> bool {
+        if self.open[index].foreign {
+            return scope != Scope::Table && scope != Scope::Select && self.is_integration_point(index);
+        }
+
+        return match scope {
+            Scope::Default => self.is_at(index, &["applet", "caption", "html", "table", "td", "th", "marquee", "object", "template"]),
+            Scope::ListItem => self.is_scope_boundary(index, Scope::Default) || self.is_at(index, &["ol", "ul"]),
+            Scope::Button => self.is_scope_boundary(index, Scope::Default) || self.is_at(index, &["button"]),
+            Scope::Table => self.is_at(index, &["html", "table", "template"]),
+            Scope::Select => !self.is_at(index, &["optgroup", "option"])
+        };
+    }
+
+    /// Index of the topmost element named one of `names` if it is in `scope`.
+    fn in_scope(&self, names: &[&str], scope: Scope) -> Option<usize> {
+        for index in (0..self.elements.len()).rev() {
+            if !self.open[index].foreign && self.is_at(index, names) {
+                return Some(index);
+            }
+
+            if self.is_scope_boundary(index, scope) {
+                return None;
+            }
+        }
+
+        return None;
+    }
+
+    fn reset_insertion_mode(&mut self) -> () {
+        for index in (0..self.elements.len()).rev() {
+            let last = index == 0;
+            self.mode = match self.elements[index].name.to_str() {
+                "select" => InsertionMode::InSelect,
+                "template" => self.template_modes.last().copied().unwrap_or(InsertionMode::InTemplate),
+                "td" | "th" if !last => InsertionMode::InCell,
+                "tr" => InsertionMode::InRow,
+                "tbody" | "thead" | "tfoot" => InsertionMode::InTableBody,
+                "caption" => InsertionMode::InCaption,
+                "colgroup" => InsertionMode::InColumnGroup,
+                "table" => InsertionMode::InTable,
+                "head" if !last => InsertionMode::InHead,
+                "body" => InsertionMode::InBody,
+                "frameset" => InsertionMode::InFrameset,
+                "html" if self.head_seen => InsertionMode::AfterHead,
+                "html" => InsertionMode::BeforeHead,
+                _ if last => InsertionMode::InBody,
+                _ => continue
+            };
+
+            return;
+        }
+
+        self.mode = InsertionMode::InBody;
+    }
+
+    fn generate_implied_end_tags(&mut self, except: Option<&str>) -> () {
+        while self.current_is(&IMPLIED_END_TAGS) && !except.is_some_and(|except| self.current_is(&[except])) {
+            self.pop(None);
+        }
+    }
+
+    /// Closes the topmost element named `name` and everything above it.
+    fn close_element(&mut self, name: &str, end_tag: Option<HtmlElement<'a>>) -> () {
+        self.generate_implied_end_tags(Some(name));
+        if let Some(index) = self.find_in_stack(name) {
+            self.pop_to(index, end_tag);
+        }
+    }
+
+    fn close_p_in_button_scope(&mut self) -> () {
+        if self.in_scope(&["p"], Scope::Button).is_some() {
+            self.close_element("p", None);
+        }
+    }
+
+    fn clear_stack_back_to(&mut self, names: &[&str]) -> () {
+        while !self.elements.is_empty() && !self.current_is(names) {
+            self.pop(None);
+        }
+    }
+
+    fn any_other_end_tag(&mut self, end_tag: HtmlElement<'a>) -> () {
+        for index in (0..self.elements.len()).rev() {
+            if self.elements[index].name.to_str() == end_tag.name.to_str() {
+                let name = end_tag.name.to_str().to_string();
+                self.generate_implied_end_tags(Some(&name));
+                self.pop_to(index, Some(end_tag));
+
+                return;
+            }
+
+            if self.is_special(index) {
+                return;
+            }
+        }
+    }
+
+    /// Streaming variant of the adoption agency algorithm. Without a furthest
+    /// block the formatting element is closed along with everything above it.
+    /// With one, the elements above the formatting element that are not
+    /// formatting elements themselves are reopened after closing, formatting
+    /// elements are reconstructed as usual once content follows.
+    fn adoption_agency(&mut self, end_tag: HtmlElement<'a>) -> () {
+        let name = end_tag.name.to_str().to_string();
+        if self.current_is(&[name.as_str()]) && !self.is_formatting_element(self.open.last().unwrap().id) {
+            self.pop(Some(end_tag));
+
+            return;
+        }
+
+        let entry = self.formatting_after_marker()
+            .rev()
+            .find(|index| self.formatting[*index].as_ref().is_some_and(|entry| entry.element.name.to_str() == name));
+
+        let Some(entry) = entry else {
+            self.any_other_end_tag(end_tag);
+
+            return;
+        };
+
+        let id = self.formatting[entry].as_ref().unwrap().id;
+        let Some(index) = self.open.iter().position(|open| open.id == id) else {
+            self.formatting.remove(entry);
+
+            return;
+        };
+
+        if (index + 1..self.elements.len()).any(|above| self.is_scope_boundary(above, Scope::Default)) {
+            return;
+        }
+
+        let has_furthest_block = (index + 1..self.elements.len()).any(|above| self.is_special(above));
+        self.formatting.remove(entry);
+
+        let mut reopened = vec![];
+        if has_furthest_block {
+            for above in index + 1..self.elements.len() {
+                if !self.is_formatting_element(self.open[above].id) {
+                    reopened.push(self.elements[above].clone());
+                }
+            }
+        }
+
+        self.pop_to(index, Some(end_tag));
+        for element in reopened {
+            self.insert_element(reopened_element(element, self.position));
+        }
+    }
+
+    fn is_formatting_element(&self, id: usize) -> bool {
+        return self.formatting.iter().flatten().any(|entry| entry.id == id);
+    }
+
+    /// Indices of the list of active formatting elements after the last
+    /// marker.
+    fn formatting_after_marker(&self) -> std::ops::Range<usize> {
+        let start = self.formatting.iter().rposition(Option::is_none).map_or(0, |marker| marker + 1);
+
+        return start..self.formatting.len();
+    }
+
+    fn push_formatting_element(&mut self, id: usize, element: HtmlElement<'a>) -> () {
+        let identical: Vec<usize> = self.formatting_after_marker()
+            .filter(|index| self.formatting[*index].as_ref().is_some_and(|entry| {
+                entry.element.name.to_str() == element.name.to_str() && entry.element.attributes == element.attributes
+            }))
+            .collect();
+
+        if identical.len() >= 3 {
+            self.formatting.remove(identical[0]);
+        }
+
+        self.formatting.push(Some(FormattingElement { id, element }));
+    }
+
+    fn reconstruct_formatting_elements(&mut self) -> () {
+        let is_open = |builder: &TreeBuilder, index: usize| match &builder.formatting[index] {
+            Some(entry) => builder.open.iter().any(|open| open.id == entry.id),
+            None => true
+        };
+
+        if self.formatting.is_empty() || is_open(self, self.formatting.len() - 1) {
+            return;
+        }
+
+        let mut start = self.formatting.len() - 1;
+        while start > 0 && !is_open(self, start - 1) {
+            start -= 1;
+        }
+
+        for index in start..self.formatting.len() {
+            let element = reopened_element(self.formatting[index].as_ref().unwrap().element.clone(), self.position);
+            let id = self.insert_element(element.clone());
+            self.formatting[index] = Some(FormattingElement { id, element });
+        }
+    }
+
+    fn clear_formatting_to_marker(&mut self) -> () {
+        while let Some(entry) = self.formatting.pop() {
+            if entry.is_none() {
+                break;
+            }
+        }
+    }
+
+    /// Where the next node goes and its depth, in front of the current table
+    /// while foster parenting.
+    fn insertion_location(&self) -> (Option<usize>, usize) {
+        let Some(current) = self.elements.last() else {
+            return (None, 0);
+        };
+
+        if self.foster_parenting && self.current_is(&["table", "tbody", "tfoot", "thead", "tr"]) {
+            if let Some(table) = self.find_in_stack("table") {
+                return (Some(self.open[table].opened_at), self.elements[table].depth);
+            }
+        }
+
+        return (self.open.last().unwrap().insert_at, current.depth + 1);
+    }
+
+    /// Places `event` at `at` in the held events, shifting every index that
+    /// points at or behind it. Returns the index of the event.
+    fn place(&mut self, event: HtmlEvent<'a>, at: Option<usize>) -> usize {
+        let Some(index) = at else {
+            self.held.push(event);
+
+            return self.held.len() - 1;
+        };
+
+        self.held.insert(index, event);
+        for open in self.open.iter_mut() {
+            if open.opened_at >= index {
+                open.opened_at += 1;
+            }
+
+            if let Some(insert_at) = open.insert_at.as_mut().filter(|insert_at| **insert_at >= index) {
+                *insert_at += 1;
+            }
+        }
+
+        return index;
+    }
+
+    /// Inserts `element` and pushes it to the stack unless it is void, returns
+    /// the id of the stack entry.
+    fn insert_element(&mut self, mut element: HtmlElement<'a>) -> usize {
+        let (at, depth) = self.insertion_location();
+        element.depth = depth;
+        element.namespace = self.namespace_of(&element);
+        let foreign = element.namespace != Namespace::Html;
+
+        let is_void = element.name.is_self_closing_element();
+        let opened_at = self.place(HtmlEvent::HtmlElementOpened { opened_element: element.clone() }, at);
+        let id = self.next_id;
+        self.next_id += 1;
+        if is_void && !foreign {
+            return id;
+        }
+
+        let insert_at = at.map(|_| opened_at + 1);
+        self.elements.push(element);
+        self.open.push(OpenElement { id, foreign, opened_at, insert_at });
+
+        return id;
+    }
+
+    /// Namespace of an element inserted at the current node, elements of
+    /// foreign content breaking out of it were handled before.
+    fn namespace_of(&self, element: &HtmlElement<'a>) -> Namespace {
+        if element.name.is_element(HtmlElementName::Svg) {
+            return Namespace::Svg;
+        }
+
+        if element.name.is_element(HtmlElementName::Math) {
+            return Namespace::MathMl;
+        }
+
+        let Some(current) = self.elements.last() else {
+            return Namespace::Html;
+        };
+
+        let index = self.elements.len() - 1;
+        if !self.open[index].foreign
+            || (self.is_integration_point(index) && !(self.is_at(index, &["mi", "mo", "mn", "ms", "mtext"]) && is(element, &["mglyph", "malignmark"])))
+        {
+            return Namespace::Html;
+        }
+
+        return current.namespace;
+    }
+
+    fn insert_synthetic(&mut self, name: &str) -> () {
+        self.insert_element(synthetic_element(name, self.position));
+    }
+
+    /// Inserts an element that is closed right away.
+    fn insert_empty(&mut self, element: HtmlElement<'a>) -> () {
+        let is_void = element.name.is_self_closing_element();
+        self.insert_element(element);
+        if !is_void {
+            self.pop(None);
+        }
+    }
+
+    fn insert_text(&mut self, content: Cow<'a, str>, span: Span) -> () {
+        let (at, depth) = self.insertion_location();
+        self.place(HtmlEvent::TextContent { content, span, depth }, at);
+    }
+
+    /// Inserts the whitespace of `content`, the frameset modes drop any
+    /// other characters.
+    fn insert_whitespace(&mut self, content: Cow<'a, str>, span: Span) -> () {
+        if content.chars().all(is_whitespace) {
+            self.insert_text(content, span);
+
+            return;
+        }
+
+        let whitespace: String = content.chars().filter(|char| is_whitespace(*char)).collect();
+        if !whitespace.is_empty() {
+            self.insert_text(Cow::Owned(whitespace), span);
+        }
+    }
+
+    fn insert_comment(&mut self, content: Cow<'a, str>, span: Span) -> () {
+        let (at, depth) = self.insertion_location();
+        self.place(HtmlEvent::Comment { content, span, depth }, at);
+    }
+
+    /// Pops the current node, the closing event carries `end_tag` if given.
+    fn pop(&mut self, end_tag: Option<HtmlElement<'a>>) -> () {
+        let (Some(element), Some(open)) = (self.elements.pop(), self.open.pop()) else {
+            return;
+        };
+
+        let mut closed_element = end_tag.unwrap_or_else(|| HtmlElement {
+            name: element.name.clone(),
+            namespace: element.namespace,
+            ..synthetic_element("html", self.position)
+        });
+
+        closed_element.depth = element.depth;
+        self.place(HtmlEvent::HtmlElementClosed { closed_element }, open.insert_at);
+    }
+
+    /// Closes a self-closing foreign element, the closing event repeats the
+    /// start tag like the tokenizer does outside of tree construction.
+    fn pop_self_closing(&mut self) -> () {
+        let (Some(element), Some(open)) = (self.elements.pop(), self.open.pop()) else {
+            return;
+        };
+
+        self.place(HtmlEvent::HtmlElementClosed { closed_element: element }, open.insert_at);
+    }
+
+    /// Pops elements until the one at `index` has been popped, its closing
+    /// event carries `end_tag` if given.
+    fn pop_to(&mut self, index: usize, end_tag: Option<HtmlElement<'a>>) -> () {
+        while self.elements.len() > index + 1 {
+            self.pop(None);
+        }
+
+        self.pop(end_tag);
+    }
+}
+
+/// Whitespace as the tree construction stage counts it.
+fn is_whitespace(char: char) -> bool {
+    return matches!(char, '\t' | '\n' | '\x0c' | '\r' | ' ');
+}
+
+/// Splits text at byte `at`, borrowed text stays borrowed.
+fn split_text(content: Cow<'_, str>, at: usize) -> (Cow<'_, str>, Cow<'_, str>) {
+    return match content {
+        Cow::Borrowed(text) => (Cow::Borrowed(&text[0..at]), Cow::Borrowed(&text[at..])),
+        Cow::Owned(text) => (Cow::Owned(text[0..at].to_string()), Cow::Owned(text[at..].to_string()))
+    };
+}
+
+fn is(element: &HtmlElement, names: &[&str]) -> bool {
+    return names.contains(&element.name.to_str());
+}
+
+/// Element implied by the tree construction, it has an empty span at
+/// `position`.
+fn synthetic_element<'a>(name: &str, position: Position) -> HtmlElement<'a> {
+    return HtmlElement {
+        name: HtmlElementName::from_str(name).unwrap(),
+        namespace: Namespace::Html,
+        attributes: HashMap::new(),
+        self_closing: false,
+        span: Span::new(position, position),
+        attribute_spans: HashMap::new(),
+        depth: 0
+    };
+}
+
+/// Copy of an element reopened at `position`, keeping its attributes.
+fn reopened_element(element: HtmlElement<'_>, position: Position) -> HtmlElement<'_> {
+    return HtmlElement { self_closing: false, span: Span::new(position, position), ..element };
+}
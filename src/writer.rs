@@ -0,0 +1,219 @@
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Write};
+
+use crate::HtmlEvent;
+use crate::dom::{Document, NodeData, NodeId};
+use crate::element::{HtmlElement, Namespace};
+use crate::position::Span;
+
+/// Serializes events or DOM nodes back into HTML.
+///
+/// Text and attribute values are escaped, except for the text of HTML raw
+/// text elements such as `script` and `style` which is written as is. Events
+/// parsed without decoding entities keep their character references, see
+/// `with_decode_entities`. Attributes are written in source order and
+/// double-quoted, void elements get no end tag and neither does `plaintext`,
+/// which runs to the end of the document. Start tag events keep their
+/// self-closing syntax. A `>` that would end a comment early is written as
+/// `&gt;`.
+pub struct HtmlWriter<W> {
+    destination: W,
+    /// Whether the text was decoded, otherwise it still holds its character
+    /// references and `&` is written as is.
+    decode_entities: bool,
+    /// Set while inside of a raw text element.
+    inside_raw_text: bool,
+    /// Span of the last self-closing start tag, the parser follows it with a
+    /// closing event of its own for foreign elements.
+    self_closed: Option<Span>
+}
+
+impl<W:Write> HtmlWriter<W> {
+    pub fn new(destination: W) -> HtmlWriter<W> {
+        return HtmlWriter::with_decode_entities(destination, true);
+    }
+
+    /// Writer for events parsed with `HtmlParserOptions::decode_entities` set
+    /// to `decode_entities`.
+    pub fn with_decode_entities(destination: W, decode_entities: bool) -> HtmlWriter<W> {
+        return HtmlWriter { destination, decode_entities, inside_raw_text: false, self_closed: None };
+    }
+
+    pub fn into_inner(self) -> W {
+        return self.destination;
+    }
+
+    pub fn write_event(&mut self, event: &HtmlEvent) -> io::Result<()> {
+        return match event {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                self.self_closed = Some(opened_element.span).filter(|_| opened_element.self_closing);
+                self.write_start_tag(opened_element, opened_element.self_closing)
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } if self.self_closed == Some(closed_element.span) => {
+                self.self_closed = None;
+                Ok(())
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => self.write_end_tag(closed_element),
+            HtmlEvent::TextContent { content, .. } => self.write_text(content),
+            HtmlEvent::Comment { content, .. } => self.write_comment(content),
+            HtmlEvent::Doctype { name, public_id, system_id, .. } => {
+                self.write_doctype(name.as_deref().unwrap_or_default(), public_id.as_deref(), system_id.as_deref())
+            }
+
+            HtmlEvent::HtmlDocumentEnd => self.destination.flush()
+        };
+    }
+
+    /// Writes `node` and everything below it, the document node writes all of
+    /// its children.
+    pub fn write_node(&mut self, document: &Document, node: NodeId) -> io::Result<()> {
+        match &document.node(node).data {
+            NodeData::Document => {}
+            NodeData::Doctype { name, public_id, system_id } => return self.write_doctype(name, public_id.as_deref(), system_id.as_deref()),
+            NodeData::Element(element) => self.write_start_tag(element, element.self_closing && element.name.is_self_closing_element())?,
+            NodeData::Text(text) => return self.write_text(text),
+            NodeData::Comment(comment) => return self.write_comment(comment)
+        }
+
+        for child in document.children(node) {
+            self.write_node(document, child)?;
+        }
+
+        if let Some(element) = document.node(node).element() {
+            self.write_end_tag(element)?;
+        }
+
+        return Ok(());
+    }
+
+    fn write_start_tag(&mut self, element: &HtmlElement, self_closing: bool) -> io::Result<()> {
+        self.inside_raw_text = is_raw_text_element(element);
+
+        return write_start_tag(&mut self.destination, element, self_closing, self.decode_entities);
+    }
+
+    fn write_end_tag(&mut self, element: &HtmlElement) -> io::Result<()> {
+        if is_raw_text_element(element) {
+            self.inside_raw_text = false;
+        }
+
+        let is_plaintext = element.namespace == Namespace::Html && element.name.to_str() == "plaintext";
+        if element.name.is_self_closing_element() || is_plaintext {
+            return Ok(());
+        }
+
+        return write!(self.destination, "</{}>", element.name.to_str());
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if self.inside_raw_text {
+            return self.destination.write_all(text.as_bytes());
+        }
+
+        return self.destination.write_all(escape(text, false, self.decode_entities).as_bytes());
+    }
+
+    fn write_comment(&mut self, comment: &str) -> io::Result<()> {
+        return write!(self.destination, "<!--{}-->", escape_comment(comment));
+    }
+
+    /// Writes the identifiers with the keyword that introduces them, a system
+    /// identifier after a public one needs none.
+    fn write_doctype(&mut self, name: &str, public_id: Option<&str>, system_id: Option<&str>) -> io::Result<()> {
+        write!(self.destination, "<!DOCTYPE")?;
+        if !name.is_empty() {
+            write!(self.destination, " {}", name)?;
+        }
+
+        if let Some(public_id) = public_id {
+            write!(self.destination, " PUBLIC {}", quote_identifier(public_id))?;
+        } else if system_id.is_some() {
+            write!(self.destination, " SYSTEM")?;
+        }
+
+        if let Some(system_id) = system_id {
+            write!(self.destination, " {}", quote_identifier(system_id))?;
+        }
+
+        return write!(self.destination, ">");
+    }
+}
+
+/// Writes the start tag of `element`, attributes in source order. `&` is only
+/// escaped in decoded attribute values.
+pub(crate) fn write_start_tag<W:Write>(destination: &mut W, element: &HtmlElement, self_closing: bool, decoded: bool) -> io::Result<()> {
+    let mut names: Vec<&str> = element.attributes.keys().map(|name| name.as_ref()).collect();
+    names.sort_by_key(|name| (element.attribute_spans.get(*name).map_or(usize::MAX, |span| span.name.start.offset), *name));
+
+    write!(destination, "<{}", element.name.to_str())?;
+    for name in names {
+        // Values that were not decoded cannot hold `&quot;`, they are quoted
+        // with `'` instead where possible.
+        let value = &element.attributes[name];
+        if !decoded && value.contains('"') && !value.contains('\'') {
+            write!(destination, " {}='{}'", name, value)?;
+        } else {
+            write!(destination, " {}=\"{}\"", name, escape(value, true, decoded))?;
+        }
+    }
+
+    if self_closing {
+        return write!(destination, "/>");
+    }
+
+    return write!(destination, ">");
+}
+
+/// Quotes a doctype identifier, which has no escapes, with the quote it does
+/// not contain.
+fn quote_identifier(identifier: &str) -> String {
+    if identifier.contains('"') {
+        return format!("'{}'", identifier);
+    }
+
+    return format!("\"{}\"", identifier);
+}
+
+/// SVG and MathML elements of the same names hold ordinary text.
+fn is_raw_text_element(element: &HtmlElement) -> bool {
+    return element.namespace == Namespace::Html
+        && matches!(element.name.to_str(), "script" | "style" | "xmp" | "iframe" | "noembed" | "noframes" | "plaintext");
+}
+
+/// Escapes the `>` of `-->` and `--!>` in a comment, and at its start where
+/// `<!-->` and `<!--->` end it as well.
+fn escape_comment(comment: &str) -> String {
+    let mut escaped = String::with_capacity(comment.len());
+    for char in comment.chars() {
+        let ends_comment = escaped.is_empty() || escaped == "-" || escaped.ends_with("--") || escaped.ends_with("--!");
+        if char == '>' && ends_comment {
+            escaped.push_str("&gt;");
+        } else {
+            escaped.push(char);
+        }
+    }
+
+    return escaped;
+}
+
+/// Escapes text as the HTML fragment serialization algorithm does, attribute
+/// values escape `"` instead of `<` and `>`. Text that was not decoded keeps
+/// its character references, so `&` and no-break spaces are written as is.
+fn escape(text: &str, in_attribute: bool, decoded: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' if decoded => escaped.push_str("&amp;"),
+            '\u{a0}' if decoded => escaped.push_str("&nbsp;"),
+            '"' if in_attribute => escaped.push_str("&quot;"),
+            '<' if !in_attribute => escaped.push_str("&lt;"),
+            '>' if !in_attribute => escaped.push_str("&gt;"),
+            _ => escaped.push(char)
+        }
+    }
+
+    return escaped;
+}
@@ -0,0 +1,60 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
+
+fn attributes<'a>(pairs: &[(&'a str, &'a str)]) -> HashMap<Cow<'a, str>, Cow<'a, str>> {
+    pairs.iter().map(|(name, value)| (Cow::Borrowed(*name), Cow::Borrowed(*value))).collect()
+}
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/attributes.html").unwrap();
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(file, options);
+    let mut opened_elements = vec![];
+    let mut texts = vec![];
+
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                opened_elements.push((opened_element.name.to_str().to_string(), opened_element.attributes));
+            }
+
+            HtmlEvent::TextContent { content, .. } => {
+                texts.push(content);
+            }
+
+            HtmlEvent::HtmlDocumentEnd => {
+                break;
+            }
+
+            _ => {
+                continue;
+            }
+        }
+    }
+
+    assert_eq!(opened_elements, vec![
+        ("input".to_string(), attributes(&[
+            ("type", "text"),
+            ("class", "a  b"),
+            ("value", "plain"),
+            ("disabled", ""),
+            ("checked", ""),
+            ("data-x", "a"),
+        ])),
+        ("div".to_string(), attributes(&[
+            ("id", "spaced"),
+            ("data-eq", "a=b=c"),
+            ("title", "say \"hi\""),
+            ("data-empty", ""),
+            ("class", "x/y"),
+        ])),
+        ("img".to_string(), attributes(&[("src", "x.png")])),
+        ("br".to_string(), attributes(&[])),
+        ("a".to_string(), attributes(&[("href", "?a=1&b=2"), ("data-gt", "1 > 0")])),
+    ]);
+    assert_eq!(texts, vec!["b", "link"]);
+}
@@ -0,0 +1,73 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
+
+fn describe(event: HtmlEvent) -> String {
+    match event {
+        HtmlEvent::HtmlElementOpened { opened_element: element } | HtmlEvent::HtmlElementClosed { closed_element: element } => {
+            let mut attributes: Vec<_> = element.attributes.into_iter().collect();
+            attributes.sort();
+
+            format!("{} {:?} {:?}", element.name.to_str(), attributes, element.span)
+        }
+
+        other => format!("{:?}", other)
+    }
+}
+
+#[test]
+fn it_works() {
+    let source = fs::read_to_string("tests/htmls/spans.html").unwrap();
+    let events: Vec<HtmlEvent> = HtmlParser::from_str(&source).map(|event| event.unwrap()).collect();
+
+    let HtmlEvent::TextContent { content, .. } = &events[1] else { panic!() };
+    assert_eq!(content, "\n  ");
+    assert!(matches!(content, Cow::Borrowed(_)));
+
+    let HtmlEvent::HtmlElementOpened { opened_element: p } = &events[2] else { panic!() };
+    assert!(p.attributes.iter().all(|(name, value)| matches!(name, Cow::Borrowed(_)) && matches!(value, Cow::Borrowed(_))));
+    assert_eq!(p.attributes["class"], "intro");
+
+    let HtmlEvent::TextContent { content, .. } = &events[3] else { panic!() };
+    assert_eq!(content, "Hello & bye");
+    assert!(!matches!(content, Cow::Borrowed(_)));
+
+    let HtmlEvent::Comment { content, .. } = &events[6] else { panic!() };
+    assert_eq!(content, " note ");
+    assert!(matches!(content, Cow::Borrowed(_)));
+}
+
+#[test]
+fn borrows_unchanged_text() {
+    let source = "<P Title=\"a&amp;b\" id=x>plain   text</P>";
+    let events: Vec<HtmlEvent> = HtmlParser::from_str(source).map(|event| event.unwrap()).collect();
+
+    let HtmlEvent::HtmlElementOpened { opened_element } = &events[0] else { panic!() };
+    let (name, value) = opened_element.attributes.get_key_value("title").unwrap();
+    assert!(!matches!(name, Cow::Borrowed(_)));
+    assert_eq!(value, "a&b");
+    assert!(matches!(opened_element.attributes["id"], Cow::Borrowed(_)));
+
+    let HtmlEvent::TextContent { content, .. } = &events[1] else { panic!() };
+    assert_eq!(content, "plain   text");
+    assert!(matches!(content, Cow::Borrowed(_)));
+
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Collapse, ..HtmlParserOptions::default() };
+    let events: Vec<HtmlEvent> = HtmlParser::from_str_with_options(source, options).map(|event| event.unwrap()).collect();
+    let HtmlEvent::TextContent { content, .. } = &events[1] else { panic!() };
+    assert_eq!(content, "plain text");
+    assert!(!matches!(content, Cow::Borrowed(_)));
+}
+
+#[test]
+fn matches_reader_events() {
+    for name in ["complex.html", "entities.html", "comments.html", "attributes.html", "malformed.html"] {
+        let path = format!("tests/htmls/{}", name);
+        let source = fs::read(&path).unwrap();
+        let borrowed: Vec<String> = HtmlParser::from_bytes(&source).map(|event| describe(event.unwrap())).collect();
+        let owned: Vec<String> = HtmlParser::new(File::open(&path).unwrap()).map(|event| describe(event.unwrap())).collect();
+
+        assert_eq!(borrowed, owned, "{}", name);
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::io::{self, Read};
+
+use htmlparser::{HtmlParser, HtmlEvent};
+use htmlparser::element::HtmlElement;
+
+struct TrickleReader<'a> {
+    data: &'a [u8],
+    step: usize
+}
+
+impl Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.step.min(buf.len()).min(self.data.len());
+        buf[..length].copy_from_slice(&self.data[..length]);
+        self.data = &self.data[length..];
+        Ok(length)
+    }
+}
+
+fn describe_element(element: &HtmlElement) -> String {
+    let mut attributes: Vec<_> = element.attributes.iter().collect();
+    attributes.sort();
+
+    format!("{} {:?} {} {:?}", element.name.to_str(), attributes, element.self_closing, element.span)
+}
+
+fn describe_events<R: Read>(parser: HtmlParser<R>) -> Vec<String> {
+    parser
+        .map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => format!("open {}", describe_element(&opened_element)),
+            HtmlEvent::HtmlElementClosed { closed_element } => format!("close {}", describe_element(&closed_element)),
+            other => format!("{:?}", other)
+        })
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    for name in ["complex.html", "spans.html", "entities.html", "comments.html", "self_closing.html"] {
+        let source = fs::read(format!("tests/htmls/{}", name)).unwrap();
+        let expected = describe_events(HtmlParser::new(&source[..]));
+
+        for step in [1, 2, 3, 7] {
+            let reader = TrickleReader { data: &source, step };
+            assert_eq!(describe_events(HtmlParser::new(reader)), expected, "{} read {} bytes at a time", name, step);
+        }
+    }
+}
+
+#[test]
+fn splits_multibyte_characters() {
+    let source = "<p title=\"zażółć\">gęślą jaźń</p>".as_bytes();
+    let reader = TrickleReader { data: source, step: 1 };
+    let texts: Vec<String> = HtmlParser::new(reader)
+        .filter_map(|event| match event.unwrap() {
+            HtmlEvent::TextContent { content, .. } => Some(content.into_owned()),
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(texts, vec!["gęślą jaźń"]);
+}
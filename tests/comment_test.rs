@@ -0,0 +1,50 @@
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
+use htmlparser::element::HtmlElementName;
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/comments.html").unwrap();
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(file, options);
+    let mut comments = vec![];
+    let mut opened_elements = vec![];
+
+    loop {
+        let event = parser.next().unwrap();
+        println!("{:#?}", event);
+
+        match event {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                opened_elements.push(opened_element.name.to_str().to_string());
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                assert!(!closed_element.name.is_element(HtmlElementName::P));
+            }
+
+            HtmlEvent::Comment { content, .. } => {
+                comments.push(content);
+            }
+
+            HtmlEvent::TextContent { content, .. } => {
+                assert_eq!("visible", content);
+            }
+
+            HtmlEvent::Doctype { .. } => {}
+
+            HtmlEvent::HtmlDocumentEnd => {
+                break;
+            }
+        }
+    }
+
+    assert_eq!(opened_elements, vec!["div", "span"]);
+    assert_eq!(comments, vec![
+        " <p class=\"hidden\">commented out</p> ",
+        "",
+        " multi\n    line -- comment ",
+        "?xml-stylesheet href=\"style.css\"?",
+    ]);
+}
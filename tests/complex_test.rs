@@ -43,7 +43,7 @@ fn it_works() {
                 }
 
                 let href = opened_element.attributes.get("href").unwrap();
-                if collected_as.contains(&href.as_str()) {
+                if collected_as.contains(&href.as_ref()) {
                     let index = collected_as.iter().position(|x| *x == href).unwrap();
                     collected_as.remove(index);
                 }
@@ -0,0 +1,55 @@
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
+use htmlparser::element::HtmlElementName;
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/custom_elements.html").unwrap();
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(file, options);
+    let mut opened_elements = vec![];
+    let mut closed_elements = vec![];
+
+    loop {
+        let event = parser.next().unwrap();
+        println!("{:#?}", event);
+
+        match event {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                if opened_element.name.is_element(HtmlElementName::Custom("my-widget".to_string())) {
+                    assert_eq!(opened_element.attributes.get("data-id"), Some(&"42".into()));
+                } else if opened_element.name.is_element(HtmlElementName::Custom("ion-button".to_string())) {
+                    assert_eq!(opened_element.attributes.get("color"), Some(&"primary".into()));
+                }
+
+                opened_elements.push(opened_element.name.to_str().to_string());
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                closed_elements.push(closed_element.name.to_str().to_string());
+            }
+
+            HtmlEvent::TextContent { content, .. } => {
+                assert_eq!("Click", content);
+            }
+
+            HtmlEvent::Comment { .. } | HtmlEvent::Doctype { .. } => {}
+
+            HtmlEvent::HtmlDocumentEnd => {
+                break;
+            }
+        }
+    }
+
+    assert_eq!(opened_elements, vec!["my-widget", "ion-button", "x-spacer"]);
+    assert_eq!(closed_elements, vec!["ion-button", "x-spacer", "my-widget"]);
+}
+
+#[test]
+fn from_str_falls_back_to_custom() {
+    assert_eq!(HtmlElementName::from_str("DIV").unwrap().to_str(), "div");
+    assert_eq!(HtmlElementName::from_str("My-Widget").unwrap().to_str(), "my-widget");
+    assert!(HtmlElementName::from_str("3d-view").is_err());
+    assert!(HtmlElementName::from_str("").is_err());
+}
@@ -0,0 +1,77 @@
+use htmlparser::{HtmlParser, HtmlEvent};
+
+fn depths(source: &str) -> Vec<(String, usize)> {
+    HtmlParser::from_str(source)
+        .map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => (format!("<{}>", opened_element.name.to_str()), opened_element.depth),
+            HtmlEvent::HtmlElementClosed { closed_element } => (format!("</{}>", closed_element.name.to_str()), closed_element.depth),
+            HtmlEvent::TextContent { content, depth, .. } => (content.into_owned(), depth),
+            HtmlEvent::Comment { content, depth, .. } => (format!("<!--{}-->", content), depth),
+            HtmlEvent::Doctype { .. } => ("<!DOCTYPE>".to_string(), 0),
+            HtmlEvent::HtmlDocumentEnd => ("end".to_string(), 0)
+        })
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    let events = depths("<!DOCTYPE html><html><body><div><br><!--c-->text</div></body></html>");
+
+    assert_eq!(events, vec![
+        ("<!DOCTYPE>".to_string(), 0),
+        ("<html>".to_string(), 0),
+        ("<body>".to_string(), 1),
+        ("<div>".to_string(), 2),
+        ("<br>".to_string(), 3),
+        ("<!--c-->".to_string(), 3),
+        ("text".to_string(), 3),
+        ("</div>".to_string(), 2),
+        ("</body>".to_string(), 1),
+        ("</html>".to_string(), 0)
+    ]);
+}
+
+#[test]
+fn closing_tag_closes_elements_opened_inside() {
+    let events = depths("<div><span></div></span>");
+
+    assert_eq!(events[2], ("</div>".to_string(), 0));
+    assert_eq!(events[3], ("</span>".to_string(), 0));
+
+    let events = depths("<svg><foreignObject><div/>z</foreignObject></svg>");
+    assert_eq!(events[4], ("</foreignObject>".to_string(), 1));
+    assert_eq!(events[5], ("</svg>".to_string(), 0));
+}
+
+#[test]
+fn unmatched_closing_tag_keeps_depth() {
+    let mut parser = HtmlParser::from_str("<div><p>a</span>b</p></div>");
+    let mut paths = vec![];
+    while !matches!(parser.next().unwrap(), HtmlEvent::HtmlDocumentEnd) {
+        paths.push(parser.path());
+    }
+
+    assert_eq!(paths, vec!["div", "div > p", "div > p", "div > p", "div > p", "div", ""]);
+}
+
+#[test]
+fn exposes_ancestors_and_path() {
+    let mut parser = HtmlParser::from_str("<html><body><div id=\"app\" class=\"main wide\"><p>Hi</p></div></body></html>");
+    let mut paths = vec![];
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::TextContent { .. } => {
+                paths.push(parser.path());
+                assert_eq!(parser.depth(), 4);
+                assert_eq!(parser.ancestors().last().unwrap().name.to_str(), "p");
+            }
+
+            HtmlEvent::HtmlDocumentEnd => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(paths, vec!["html > body > div#app.main.wide > p"]);
+    assert_eq!(parser.depth(), 0);
+    assert_eq!(parser.path(), "");
+}
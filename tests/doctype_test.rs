@@ -0,0 +1,121 @@
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::doctype::QuirksMode;
+
+type Doctype = (Option<String>, Option<String>, Option<String>, bool);
+
+fn doctype(source: &str) -> Doctype {
+    for event in HtmlParser::from_str(source) {
+        if let HtmlEvent::Doctype { name, public_id, system_id, force_quirks, .. } = event.unwrap() {
+            return (name.map(Into::into), public_id.map(Into::into), system_id.map(Into::into), force_quirks);
+        }
+    }
+
+    panic!("no doctype in {:?}", source);
+}
+
+fn quirks_mode(source: &str) -> QuirksMode {
+    let mut parser = HtmlParser::from_str(source);
+    while !matches!(parser.next().unwrap(), HtmlEvent::HtmlDocumentEnd) {}
+
+    parser.quirks_mode()
+}
+
+fn some(value: &str) -> Option<String> {
+    Some(value.to_string())
+}
+
+#[test]
+fn it_works() {
+    let mut parser = HtmlParser::from_str("<!DOCTYPE html><p>");
+    match parser.next().unwrap() {
+        HtmlEvent::Doctype { name, public_id, system_id, force_quirks, span } => {
+            assert_eq!(name.as_deref(), Some("html"));
+            assert_eq!((public_id, system_id, force_quirks), (None, None, false));
+            assert_eq!((span.start.offset, span.end.offset), (0, 15));
+        }
+
+        event => panic!("unexpected {:?}", event)
+    }
+
+    assert!(matches!(parser.next().unwrap(), HtmlEvent::HtmlElementOpened { opened_element } if opened_element.name.to_str() == "p"));
+}
+
+#[test]
+fn parses_identifiers() {
+    assert_eq!(
+        doctype("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'http://www.w3.org/TR/html4/strict.dtd'>"),
+        (some("html"), some("-//W3C//DTD HTML 4.01//EN"), some("http://www.w3.org/TR/html4/strict.dtd"), false)
+    );
+
+    assert_eq!(doctype("<!doctype html system \"about:legacy-compat\">"), (some("html"), None, some("about:legacy-compat"), false));
+    assert_eq!(doctype("<!DOCTYPEhtml>"), (some("html"), None, None, false));
+}
+
+#[test]
+fn forces_quirks_for_broken_declarations() {
+    assert_eq!(doctype("<!DOCTYPE>"), (None, None, None, true));
+    assert_eq!(doctype("<!DOCTYPE html PUBLIC>"), (some("html"), None, None, true));
+    assert_eq!(doctype("<!DOCTYPE html PUBLIC \"foo>"), (some("html"), some("foo"), None, true));
+    assert_eq!(doctype("<!DOCTYPE html bogus>"), (some("html"), None, None, true));
+    assert_eq!(doctype("<!DOCTYPE html"), (some("html"), None, None, true));
+
+    // Garbage after the system identifier is ignored.
+    assert_eq!(doctype("<!DOCTYPE html SYSTEM \"a\" b>"), (some("html"), None, some("a"), false));
+}
+
+#[test]
+fn treats_other_declarations_as_comments() {
+    let events: Vec<String> = HtmlParser::from_str("<!DOC><!ELEMENT br EMPTY><!>")
+        .map(|event| match event.unwrap() {
+            HtmlEvent::Comment { content, .. } => content.into_owned(),
+            event => panic!("unexpected {:?}", event)
+        })
+        .collect();
+
+    assert_eq!(events, vec!["DOC", "ELEMENT br EMPTY", ""]);
+}
+
+#[test]
+fn computes_the_quirks_mode() {
+    assert_eq!(quirks_mode("<!DOCTYPE html><p>"), QuirksMode::NoQuirks);
+    assert_eq!(quirks_mode("<!-- first --><!DOCTYPE html><p>"), QuirksMode::NoQuirks);
+    assert_eq!(quirks_mode("<p>no doctype"), QuirksMode::Quirks);
+    assert_eq!(quirks_mode("<!DOCTYPE svg>"), QuirksMode::Quirks);
+    assert_eq!(quirks_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 3.2 Final//EN\">"), QuirksMode::Quirks);
+    assert_eq!(quirks_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\">"), QuirksMode::Quirks);
+    assert_eq!(
+        quirks_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\">"),
+        QuirksMode::LimitedQuirks
+    );
+
+    assert_eq!(
+        quirks_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">"),
+        QuirksMode::NoQuirks
+    );
+}
+
+#[test]
+fn quirks_mode_keeps_paragraphs_around_tables() {
+    let options = HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() };
+    let depths = |source| -> Vec<(String, usize)> {
+        HtmlParser::from_str_with_options(source, options.clone())
+            .filter_map(|event| match event.unwrap() {
+                HtmlEvent::HtmlElementOpened { opened_element } => Some((opened_element.name.to_str().to_string(), opened_element.depth)),
+                _ => None
+            })
+            .collect()
+    };
+
+    assert_eq!(depths("<p><table>")[3..], [("p".to_string(), 2), ("table".to_string(), 3)]);
+    assert_eq!(depths("<!DOCTYPE html><p><table>")[3..], [("p".to_string(), 2), ("table".to_string(), 2)]);
+    assert_eq!(depths("<!DOCTYPE html PUBLIC \"-//W3O//DTD W3 HTML 3.0//EN//\"><p><table>")[3..], [("p".to_string(), 2), ("table".to_string(), 3)]);
+}
+
+#[test]
+fn reports_unterminated_doctypes() {
+    let options = HtmlParserOptions { strict: true, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::from_str_with_options("<!DOCTYPE html", options);
+
+    assert!(matches!(parser.next(), Ok(HtmlEvent::Doctype { force_quirks: true, .. })));
+    assert!(parser.next().is_err());
+}
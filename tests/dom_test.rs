@@ -0,0 +1,74 @@
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, WhitespaceMode};
+use htmlparser::dom::{Document, NodeData, NodeId};
+
+fn element_names(document: &Document, ids: impl Iterator<Item = NodeId>) -> Vec<String> {
+    ids
+        .filter_map(|id| document.node(id).element())
+        .map(|element| element.name.to_str().to_string())
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/example.html").unwrap();
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    let document = Document::parse(HtmlParser::with_options(file, options)).unwrap();
+
+    let children: Vec<NodeId> = document.children(document.root()).collect();
+    assert_eq!(children.len(), 2);
+    assert!(matches!(&document.node(children[0]).data, NodeData::Doctype { name, .. } if name == "html"));
+
+    let html = children[1];
+    assert_eq!(element_names(&document, document.children(html)), vec!["head", "body"]);
+
+    let head = document.node(html).first_child().unwrap();
+    assert_eq!(
+        element_names(&document, document.children(head)),
+        vec!["meta", "meta", "meta", "title", "link", "script"]
+    );
+
+    let title = document.children(head).nth(3).unwrap();
+    assert_eq!(document.text_content(title), "HTML 5 Kórwa");
+    assert_eq!(document.node(title).parent(), Some(head));
+    assert_eq!(document.node(document.node(title).previous_sibling().unwrap()).element().unwrap().name.to_str(), "meta");
+    assert_eq!(document.node(document.node(title).next_sibling().unwrap()).element().unwrap().name.to_str(), "link");
+
+    let body = document.node(html).last_child().unwrap();
+    assert_eq!(element_names(&document, document.descendants(body)), vec!["script"]);
+}
+
+#[test]
+fn keeps_text_and_comments_in_order() {
+    let document = Document::from_str("<div>a<!-- b --><span>c</span>d</div>").unwrap();
+    let div = document.node(document.root()).first_child().unwrap();
+    let children: Vec<String> = document.children(div)
+        .map(|id| match &document.node(id).data {
+            NodeData::Text(text) => format!("text {}", text),
+            NodeData::Comment(comment) => format!("comment {}", comment),
+            NodeData::Element(element) => format!("element {}", element.name.to_str()),
+            _ => unreachable!()
+        })
+        .collect();
+
+    assert_eq!(children, vec!["text a", "comment  b ", "element span", "text d"]);
+    assert_eq!(document.text_content(div), "acd");
+}
+
+#[test]
+fn follows_parser_stack() {
+    // Void elements get no children, a closing tag closes the elements opened
+    // inside of it and stray closing tags are ignored, just like in the
+    // parser's own open-element stack.
+    let document = Document::from_str("<div><br>a<span>b</div>c</span>d</div>e<hr>").unwrap();
+    let root = document.root();
+    let div = document.node(root).first_child().unwrap();
+    let span = document.children(div).nth(2).unwrap();
+
+    assert_eq!(element_names(&document, document.descendants(root)), vec!["div", "br", "span", "hr"]);
+    assert_eq!(document.children(document.node(div).first_child().unwrap()).count(), 0);
+    assert_eq!(document.text_content(span), "b");
+    assert_eq!(document.text_content(div), "ab");
+    assert_eq!(document.text_content(root), "abcde");
+}
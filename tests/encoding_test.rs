@@ -0,0 +1,185 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, Read};
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::encoding::{Confidence, Encoding, InvalidUtf8};
+use htmlparser::error::ParseError;
+
+struct TrickleReader<'a> {
+    data: &'a [u8],
+    step: usize
+}
+
+impl Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.step.min(buf.len()).min(self.data.len());
+        buf[..length].copy_from_slice(&self.data[..length]);
+        self.data = &self.data[length..];
+        Ok(length)
+    }
+}
+
+/// Collects the text of the document without the whitespace between tags,
+/// the parser is borrowed so that the sniffed encoding can be checked
+/// afterwards.
+fn texts<R: Read>(parser: &mut HtmlParser<R>) -> Vec<String> {
+    let mut texts = vec![];
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::TextContent { content, .. } if !content.trim().is_empty() => texts.push(content.into_owned()),
+            HtmlEvent::HtmlDocumentEnd => return texts,
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut parser = HtmlParser::new(File::open("tests/encodings/iso_8859_2.html").unwrap());
+
+    assert_eq!(texts(&mut parser), vec!["Pchnąć w tę łódź jeża", "Zażółć gęślą jaźń"]);
+    assert_eq!(parser.encoding(), Encoding::Iso8859_2);
+    assert_eq!(parser.encoding_confidence(), Confidence::Tentative);
+}
+
+#[test]
+fn reads_http_equiv_declarations() {
+    let source = fs::read("tests/encodings/windows_1250.html").unwrap();
+    let mut parser = HtmlParser::from_bytes(&source);
+
+    // The commented out declaration is skipped by the prescan.
+    assert_eq!(texts(&mut parser), vec!["Cennik – „promocja”", "Zażółć gęślą jaźń"]);
+    assert_eq!(parser.encoding(), Encoding::Windows1250);
+
+    for step in [1, 2, 5] {
+        let mut parser = HtmlParser::new(TrickleReader { data: &source, step });
+        assert_eq!(texts(&mut parser), vec!["Cennik – „promocja”", "Zażółć gęślą jaźń"], "read {} bytes at a time", step);
+    }
+}
+
+#[test]
+fn prefers_the_transport_encoding() {
+    let source = fs::read("tests/encodings/iso_8859_2.html").unwrap();
+    let options = HtmlParserOptions { transport_encoding: Encoding::for_label("Windows-1252"), ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(&source[..], options);
+
+    assert_eq!(texts(&mut parser)[1], "Za¿ó³æ gê¶l± ja¼ñ");
+    assert_eq!(parser.encoding(), Encoding::Windows1252);
+    assert_eq!(parser.encoding_confidence(), Confidence::Certain);
+}
+
+#[test]
+fn strips_byte_order_marks() {
+    let mut parser = HtmlParser::from_bytes(b"\xef\xbb\xbf<meta charset=latin2><p>\xc5\xbc</p>");
+    match parser.next().unwrap() {
+        HtmlEvent::HtmlElementOpened { opened_element } => assert_eq!(opened_element.span.start.offset, 3),
+        event => panic!("unexpected {:?}", event)
+    }
+
+    assert_eq!(texts(&mut parser), vec!["ż"]);
+    assert_eq!((parser.encoding(), parser.encoding_confidence()), (Encoding::Utf8, Confidence::Certain));
+
+    let mut source = vec![0xff, 0xfe];
+    source.extend("<p>ż😀</p>".encode_utf16().flat_map(u16::to_le_bytes));
+    for step in [1, 3, 64] {
+        let mut parser = HtmlParser::new(TrickleReader { data: &source, step });
+        assert_eq!(texts(&mut parser), vec!["ż😀"]);
+        assert_eq!(parser.encoding(), Encoding::Utf16Le);
+    }
+}
+
+#[test]
+fn strips_byte_order_marks_from_both_constructors() {
+    let source = "\u{feff}<p>a</p>";
+    for mut parser in [HtmlParser::from_str(source), HtmlParser::from_bytes(source.as_bytes())] {
+        let events: Vec<_> = (0..3).map(|_| parser.next().unwrap()).collect();
+        assert!(matches!(&events[0], HtmlEvent::HtmlElementOpened { opened_element } if opened_element.span.start.offset == 3));
+        assert!(matches!(&events[1], HtmlEvent::TextContent { content: Cow::Borrowed("a"), span, .. } if span.start.offset == 6));
+        assert!(matches!(&events[2], HtmlEvent::HtmlElementClosed { .. }));
+    }
+}
+
+#[test]
+fn replaces_broken_utf16() {
+    let mut source = vec![0xfe, 0xff];
+    source.extend("<p>a".encode_utf16().flat_map(u16::to_be_bytes));
+    source.extend([0xd8, 0x3d, 0x00, 0x62, 0x00]);
+    let mut parser = HtmlParser::new(&source[..]);
+
+    assert_eq!(texts(&mut parser), vec!["a\u{fffd}b\u{fffd}"]);
+}
+
+#[test]
+fn does_not_sniff_text() {
+    let mut parser = HtmlParser::from_str("<meta charset=iso-8859-2><p>ż</p>");
+
+    assert_eq!(texts(&mut parser), vec!["ż"]);
+    assert_eq!((parser.encoding(), parser.encoding_confidence()), (Encoding::Utf8, Confidence::Irrelevant));
+}
+
+/// Text and `data-price` values of the corrupted fixture read `step` bytes
+/// at a time, with the offsets of the reported errors.
+fn corrupted(invalid_utf8: InvalidUtf8, step: usize) -> (Vec<String>, Vec<usize>) {
+    let source = fs::read("tests/encodings/corrupted.html").unwrap();
+    let options = HtmlParserOptions { invalid_utf8, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(TrickleReader { data: &source, step }, options);
+    let mut texts = vec![];
+    let mut errors = vec![];
+    loop {
+        match parser.next() {
+            Ok(HtmlEvent::HtmlElementOpened { opened_element }) => {
+                texts.extend(opened_element.attributes.get("data-price").map(|value| value.to_string()));
+            }
+
+            Ok(HtmlEvent::TextContent { content, .. }) if !content.trim().is_empty() => texts.push(content.into_owned()),
+            Ok(HtmlEvent::HtmlDocumentEnd) => return (texts, errors),
+            Ok(_) => {}
+            Err(ParseError::InvalidUtf8 { position }) => errors.push(position.offset),
+            Err(error) => panic!("unexpected {:?}", error)
+        }
+    }
+}
+
+#[test]
+fn replaces_invalid_utf8() {
+    let expected = [
+        "Caf\u{fffd} Zaj\u{fffd}c",
+        "10 \u{fffd} zł",
+        "Stray \u{fffd} byte",
+        "Overlong \u{fffd}\u{fffd} slash",
+        "Cut off \u{fffd}"
+    ];
+
+    for step in [1, 2, 64] {
+        assert_eq!(corrupted(InvalidUtf8::Replace, step), (expected.iter().map(|text| text.to_string()).collect(), vec![]));
+    }
+
+    let source = fs::read("tests/encodings/corrupted.html").unwrap();
+    assert_eq!(texts(&mut HtmlParser::from_bytes(&source))[0], expected[0]);
+}
+
+#[test]
+fn reports_invalid_utf8() {
+    let (texts, errors) = corrupted(InvalidUtf8::Error, 3);
+
+    assert_eq!(texts, vec!["Caf Zajc", "10  zł", "Stray  byte", "Overlong  slash", "Cut off "]);
+    assert_eq!(errors, vec![44, 49, 97, 111, 138, 139, 166]);
+}
+
+#[test]
+fn passes_invalid_utf8_through_as_latin1() {
+    let (texts, errors) = corrupted(InvalidUtf8::Latin1, 5);
+
+    assert_eq!(texts, vec!["Café Zaj±c", "10 \u{e2}\u{82} zł", "Stray ÿ byte", "Overlong À¯ slash", "Cut off ð\u{9f}\u{98}"]);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn looks_up_labels() {
+    assert_eq!(Encoding::for_label(" LATIN2 "), Some(Encoding::Iso8859_2));
+    assert_eq!(Encoding::for_label("iso-8859-1"), Some(Encoding::Windows1252));
+    assert_eq!(Encoding::for_label("utf-16"), Some(Encoding::Utf16Le));
+    assert_eq!(Encoding::for_label("koi8-r"), None);
+    assert_eq!(Encoding::Windows1250.name(), "windows-1250");
+}
@@ -0,0 +1,70 @@
+use std::borrow::Cow;
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::entities;
+
+fn collect_paragraph(options: HtmlParserOptions) -> (String, String) {
+    let file = File::open("tests/htmls/entities.html").unwrap();
+    let mut parser = HtmlParser::with_options(file, options);
+    let mut title = String::new();
+    let mut text = String::new();
+
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                title = opened_element.attributes.get("title").unwrap().to_string();
+            }
+
+            HtmlEvent::TextContent { content, .. } if !content.trim().is_empty() => {
+                text = content.into_owned();
+            }
+
+            HtmlEvent::HtmlDocumentEnd => {
+                break;
+            }
+
+            _ => {
+                continue;
+            }
+        }
+    }
+
+    (title, text)
+}
+
+#[test]
+fn it_works() {
+    let (title, text) = collect_paragraph(HtmlParserOptions::default());
+
+    assert_eq!(title, "Fish & Chips &notit; &copy=1 A");
+    assert_eq!(text, "Tom & Jerry <3 \u{2014} \u{2014} \u{a0}\u{ac}it; \u{fffd} \u{20ac} &bogus; &");
+}
+
+#[test]
+fn keeps_raw_text() {
+    let options = HtmlParserOptions { decode_entities: false, ..HtmlParserOptions::default() };
+    let (title, text) = collect_paragraph(options);
+
+    assert_eq!(title, "Fish &amp; Chips &notit; &copy=1 &#x41;");
+    assert_eq!(text, "Tom &amp; Jerry &lt;3 &#x2014; &#8212; &nbsp;&notit; &#0; &#x80; &bogus; &amp");
+}
+
+#[test]
+fn decodes_references() {
+    assert_eq!(entities::decode("no references", false), "no references");
+    assert_eq!(entities::decode("&lt;&gt;&quot;&apos;", false), "<>\"'");
+    assert_eq!(entities::decode("&#X1F600;&#128512", false), "\u{1f600}\u{1f600}");
+    assert_eq!(entities::decode("&#xD800;&#1114112;&#x;&#", false), "\u{fffd}\u{fffd}&#x;&#");
+    assert_eq!(entities::decode("&NotNestedGreaterGreater;", false), "\u{2aa2}\u{338}");
+    assert_eq!(entities::decode("?a=1&copy=2", true), "?a=1&copy=2");
+    assert_eq!(entities::decode("?a=1&copy=2", false), "?a=1\u{a9}=2");
+    assert_eq!(entities::decode("&ampx &amp;x", true), "&ampx &x");
+}
+
+#[test]
+fn borrows_values_without_references() {
+    assert!(matches!(entities::decode("a & b", false), Cow::Borrowed("a & b")));
+    assert!(matches!(entities::decode("&foo &#; ?a=1&copy=2", true), Cow::Borrowed(_)));
+    assert!(matches!(entities::decode("a &amp; b", false), Cow::Owned(value) if value == "a & b"));
+}
@@ -1,12 +1,13 @@
 use std::fs::File;
 
-use htmlparser::{HtmlParser, HtmlEvent};
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
 use htmlparser::element::HtmlElementName;
 
 #[test]
 fn it_works() {
     let file = File::open("tests/htmls/entry.html").unwrap();
-    let mut parser = HtmlParser::new(file);
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(file, options);
     let mut counter = 0;
     loop {
         let event = parser.next().unwrap();
@@ -17,8 +18,8 @@ fn it_works() {
             HtmlEvent::HtmlElementOpened { opened_element } => {
                 if counter == 1 {
                     assert_eq!(HtmlElementName::Div.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("class"), Some(&"test test_eq".to_string()));
-                    assert_eq!(opened_element.attributes.get("data-value"), Some(&"test".to_string()));
+                    assert_eq!(opened_element.attributes.get("class"), Some(&"test test_eq".into()));
+                    assert_eq!(opened_element.attributes.get("data-value"), Some(&"test".into()));
                 } else if counter == 2 {
                     assert_eq!(HtmlElementName::P.to_str(), opened_element.name.to_str());
                 } else if counter == 3 {
@@ -36,11 +37,13 @@ fn it_works() {
                 }
             }
 
-            HtmlEvent::TextContent(content) => {
+            HtmlEvent::TextContent { content, .. } => {
                 assert_eq!(counter, 4);
                 assert_eq!("text content", content);
             }
 
+            HtmlEvent::Comment { .. } | HtmlEvent::Doctype { .. } => {}
+
             HtmlEvent::HtmlDocumentEnd => {
                 assert_eq!(counter, 8);
 
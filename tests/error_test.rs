@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::encoding::InvalidUtf8;
+use htmlparser::error::ParseError;
+
+struct FailingReader {
+    remaining: &'static [u8]
+}
+
+impl Read for FailingReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.remaining.is_empty() {
+            return Err(io::Error::other("connection reset"));
+        }
+
+        let length = self.remaining.read(buffer)?;
+
+        Ok(length)
+    }
+}
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/malformed.html").unwrap();
+    let options = HtmlParserOptions { strict: true, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(file, options);
+    let mut errors = vec![];
+
+    loop {
+        match parser.next() {
+            Ok(HtmlEvent::HtmlElementOpened { opened_element }) => {
+                assert_eq!(opened_element.attributes.get("class"), Some(&"a".into()));
+                assert_eq!(opened_element.attributes.get("id"), Some(&"b".into()));
+                assert_eq!(opened_element.attributes.get("title"), Some(&"x\"y".into()));
+                assert_eq!(opened_element.attributes.get("data-a"), Some(&"1".into()));
+            }
+
+            Ok(HtmlEvent::HtmlDocumentEnd) => {
+                break;
+            }
+
+            Ok(_) => {
+                continue;
+            }
+
+            Err(error) => {
+                println!("{}", error);
+                errors.push(error);
+            }
+        }
+    }
+
+    assert_eq!(errors.len(), 4);
+    assert!(matches!(&errors[0], ParseError::MalformedAttribute { name, .. } if name == "class"));
+    assert_eq!((errors[0].position().line, errors[0].position().column), (1, 15));
+    assert!(matches!(&errors[1], ParseError::MalformedAttribute { name, .. } if name == "title"));
+    assert!(matches!(&errors[2], ParseError::DuplicateAttribute { name, .. } if name == "data-a"));
+    assert!(matches!(&errors[3], ParseError::UnexpectedEof { .. }));
+    assert_eq!(errors[3].position().offset, 60);
+    assert_eq!((errors[3].position().line, errors[3].position().column), (2, 1));
+    assert_eq!(errors[3].to_string(), "unexpected end of file in tag at line 2, column 1 (byte 60)");
+}
+
+#[test]
+fn recovers_silently_by_default() {
+    let file = File::open("tests/htmls/malformed.html").unwrap();
+    let mut parser = HtmlParser::new(file);
+
+    loop {
+        if let HtmlEvent::HtmlDocumentEnd = parser.next().unwrap() {
+            break;
+        }
+    }
+}
+
+#[test]
+fn reports_invalid_utf8() {
+    let options = HtmlParserOptions { invalid_utf8: InvalidUtf8::Error, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(&b"<p>a\xffb</p>"[..], options);
+
+    assert!(matches!(parser.next(), Ok(HtmlEvent::HtmlElementOpened { .. })));
+    match parser.next() {
+        Err(ParseError::InvalidUtf8 { position }) => assert_eq!(position.offset, 4),
+        event => panic!("unexpected event {:?}", event)
+    }
+    assert!(matches!(parser.next(), Ok(HtmlEvent::TextContent { content, .. }) if content == "ab"));
+    assert!(matches!(parser.next(), Ok(HtmlEvent::HtmlElementClosed { .. })));
+    assert!(matches!(parser.next(), Ok(HtmlEvent::HtmlDocumentEnd)));
+}
+
+#[test]
+fn propagates_io_errors() {
+    let mut parser = HtmlParser::new(FailingReader { remaining: b"<p>text" });
+
+    assert!(matches!(parser.next(), Ok(HtmlEvent::HtmlElementOpened { .. })));
+    assert!(matches!(parser.next(), Ok(HtmlEvent::TextContent { content, .. }) if content == "text"));
+    match parser.next() {
+        Err(ParseError::Io { error, position }) => {
+            assert_eq!(error.to_string(), "connection reset");
+            assert_eq!(position.offset, 7);
+        }
+        event => panic!("unexpected event {:?}", event)
+    }
+    assert!(matches!(parser.next(), Ok(HtmlEvent::HtmlDocumentEnd)));
+}
+
+#[test]
+fn emits_text_read_before_io_errors() {
+    let parser = HtmlParser::new(FailingReader { remaining: b"<p>a &amp; b</p>c <!-- d" });
+    let events: Vec<String> = parser
+        .map(|event| match event {
+            Ok(HtmlEvent::TextContent { content, .. }) => format!("{:?}", content),
+            Ok(HtmlEvent::Comment { .. }) => "comment".to_string(),
+            Ok(_) => "tag".to_string(),
+            Err(error) => error.to_string()
+        })
+        .collect();
+
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[..4], ["tag", "\"a & b\"", "tag", "\"c \""]);
+    assert!(events[4].contains("connection reset"), "{}", events[4]);
+}
@@ -1,12 +1,13 @@
 use std::fs::File;
 
-use htmlparser::{HtmlParser, HtmlEvent};
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
 use htmlparser::element::HtmlElementName;
 
 #[test]
 fn it_works() {
     let file = File::open("tests/htmls/example.html").unwrap();
-    let mut parser = HtmlParser::new(file);
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::with_options(file, options);
     let mut counter = 0;
 
     loop {
@@ -15,39 +16,42 @@ fn it_works() {
         println!("{:#?}", event);
 
         match event {
+            HtmlEvent::Doctype { name, public_id, system_id, force_quirks, .. } => {
+                assert_eq!(counter, 1);
+                assert_eq!(name.as_deref(), Some("html"));
+                assert_eq!((public_id, system_id, force_quirks), (None, None, false));
+            }
+
             HtmlEvent::HtmlElementOpened { opened_element } => {
-                if counter == 1 {
-                    assert_eq!(HtmlElementName::Doctype.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("html"), Some(&String::new()));
-                } else if counter == 2 {
+                if counter == 2 {
                     assert_eq!(HtmlElementName::Html.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("lang"), Some(&"en".to_string()));
+                    assert_eq!(opened_element.attributes.get("lang"), Some(&"en".into()));
                 } else if counter == 3 {
                     assert_eq!(HtmlElementName::Head.to_str(), opened_element.name.to_str());
                 } else if counter == 4 {
                     assert_eq!(HtmlElementName::Meta.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("charset"), Some(&"UTF-8".to_string()));
+                    assert_eq!(opened_element.attributes.get("charset"), Some(&"UTF-8".into()));
                 } else if counter == 5 {
                     assert_eq!(HtmlElementName::Meta.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("name"), Some(&"viewport".to_string()));
-                    assert_eq!(opened_element.attributes.get("content"), Some(&"width=device-width, initial-scale=1.0".to_string()));
+                    assert_eq!(opened_element.attributes.get("name"), Some(&"viewport".into()));
+                    assert_eq!(opened_element.attributes.get("content"), Some(&"width=device-width, initial-scale=1.0".into()));
                 } else if counter == 6 {
                     assert_eq!(HtmlElementName::Meta.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("http-equiv"), Some(&"X-UA-Compatible".to_string()));
-                    assert_eq!(opened_element.attributes.get("content"), Some(&"ie=edge".to_string()));
+                    assert_eq!(opened_element.attributes.get("http-equiv"), Some(&"X-UA-Compatible".into()));
+                    assert_eq!(opened_element.attributes.get("content"), Some(&"ie=edge".into()));
                 } else if counter == 7 {
                     assert_eq!(HtmlElementName::Title.to_str(), opened_element.name.to_str());
                 } else if counter == 10 {
                     assert_eq!(HtmlElementName::Link.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("rel"), Some(&"stylesheet".to_string()));
-                    assert_eq!(opened_element.attributes.get("href"), Some(&"style.css".to_string()));
+                    assert_eq!(opened_element.attributes.get("rel"), Some(&"stylesheet".into()));
+                    assert_eq!(opened_element.attributes.get("href"), Some(&"style.css".into()));
                 } else if counter == 11 {
                     assert!(opened_element.name.is_element(HtmlElementName::Script));
                 } else if counter == 15 {
                     assert_eq!(HtmlElementName::Body.to_str(), opened_element.name.to_str());
                 } else if counter == 16 {
                     assert_eq!(HtmlElementName::Script.to_str(), opened_element.name.to_str());
-                    assert_eq!(opened_element.attributes.get("src"), Some(&"index.js".to_string()));
+                    assert_eq!(opened_element.attributes.get("src"), Some(&"index.js".into()));
                 }
             }
 
@@ -67,7 +71,7 @@ fn it_works() {
                 }
             }
 
-            HtmlEvent::TextContent(content) => {
+            HtmlEvent::TextContent { content, .. } => {
                 if counter == 8 {
                     assert_eq!("HTML 5 Kórwa".to_string(), content);
                 } else if counter == 12 {
@@ -75,6 +79,8 @@ fn it_works() {
                 }
             }
 
+            HtmlEvent::Comment { .. } => {}
+
             HtmlEvent::HtmlDocumentEnd => {
                 assert_eq!(counter, 20);
 
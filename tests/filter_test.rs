@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
+use htmlparser::dom::Document;
+use htmlparser::filter::HtmlFilter;
+
+fn describe(event: HtmlEvent) -> String {
+    match event {
+        HtmlEvent::HtmlElementOpened { opened_element } => format!("<{}>", opened_element.name.to_str()),
+        HtmlEvent::HtmlElementClosed { closed_element } => format!("</{}>", closed_element.name.to_str()),
+        HtmlEvent::TextContent { content, .. } => content.into_owned(),
+        other => format!("{:?}", other)
+    }
+}
+
+fn filter(source: &str, selectors: &str) -> Vec<String> {
+    let options = HtmlParserOptions { whitespace: WhitespaceMode::Trim, ..HtmlParserOptions::default() };
+    HtmlFilter::new(HtmlParser::from_str_with_options(source, options), selectors)
+        .unwrap()
+        .map(|event| describe(event.unwrap()))
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/selectors.html").unwrap();
+    let events: Vec<String> = HtmlFilter::new(HtmlParser::new(file), "div.card > a[href]")
+        .unwrap()
+        .map(|event| describe(event.unwrap()))
+        .collect();
+
+    assert_eq!(events, vec!["<a>", "Read", "</a>"]);
+}
+
+#[test]
+fn passes_whole_matching_elements() {
+    let source = fs::read_to_string("tests/htmls/selectors.html").unwrap();
+
+    assert_eq!(filter(&source, "[data-lang=pl]"), vec![
+        "<div>", "<h2>", "Second", "</h2>", "<span>", "</span>", "<p>", "Body", "</p>", "<p>", "More", "</p>", "</div>"
+    ]);
+    assert_eq!(filter(&source, "body li"), vec![
+        "<li>", "1", "</li>", "<li>", "2", "</li>", "<li>", "3", "</li>", "<li>", "4", "</li>", "<li>", "5", "</li>"
+    ]);
+    assert_eq!(filter(&source, "#menu .legacy, h2"), vec![
+        "<a>", "Old", "</a>", "<h2>", "First", "</h2>", "<h2>", "Second", "</h2>"
+    ]);
+}
+
+#[test]
+fn handles_void_and_foreign_elements() {
+    let source = "<p><img src=a>text<svg><circle r='1'/><g>x</g></svg></p>";
+
+    assert_eq!(filter(source, "p > img"), vec!["<img>"]);
+    assert_eq!(filter(source, "svg circle"), vec!["<circle>", "</circle>"]);
+    assert_eq!(filter(source, "p circle, g"), vec!["<circle>", "</circle>", "<g>", "x", "</g>"]);
+}
+
+#[test]
+fn ends_matches_with_implied_closes() {
+    assert_eq!(filter("<div class=x><p>a</div><span>leak</span>", "div.x"), vec!["<div>", "<p>", "a", "</div>"]);
+    assert_eq!(filter("<ul><li class=t>a<li>b</ul><p>c</p>", "li.t"), vec!["<li>", "a", "<li>", "b", "</li>"]);
+    assert_eq!(filter("<div><p>x</div><p>y", "p"), vec!["<p>", "x", "</p>", "<p>", "y", "</p>"]);
+
+    let mut html_filter = HtmlFilter::new(HtmlParser::from_str("<div><p>x</div>"), "p").unwrap();
+    let events: Vec<HtmlEvent> = (0..3).map(|_| html_filter.next().unwrap()).collect();
+    let HtmlEvent::HtmlElementClosed { closed_element } = &events[2] else { panic!() };
+    assert_eq!((closed_element.name.to_str(), closed_element.depth), ("p", 1));
+    assert_eq!((closed_element.span.start.offset, closed_element.span.end.offset), (9, 9));
+    assert!(matches!(html_filter.next(), Ok(HtmlEvent::HtmlDocumentEnd)));
+
+    let options = HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() };
+    let events: Vec<String> = HtmlFilter::new(HtmlParser::from_str_with_options("<div class=x><p>a</div><span>leak</span>", options), "div.x")
+        .unwrap()
+        .map(|event| describe(event.unwrap()))
+        .collect();
+
+    assert_eq!(events, vec!["<div>", "<p>", "a", "</p>", "</div>"]);
+}
+
+#[test]
+fn matches_like_the_dom() {
+    let source = fs::read_to_string("tests/htmls/complex.html").unwrap();
+    let document = Document::from_str(&source).unwrap();
+
+    for selectors in ["a[href]", "div.container a", "li > a[href^=http]", "footer *"] {
+        let streamed = HtmlFilter::new(HtmlParser::from_str(&source), selectors)
+            .unwrap()
+            .map(|event| event.unwrap())
+            .filter(|event| matches!(event, HtmlEvent::HtmlElementOpened { .. }))
+            .count();
+
+        let mut elements = HashSet::new();
+        for node in document.select(selectors).unwrap() {
+            elements.insert(node);
+            elements.extend(document.descendants(node).filter(|id| document.node(*id).element().is_some()));
+        }
+
+        assert_eq!(streamed, elements.len(), "{}", selectors);
+    }
+}
+
+#[test]
+fn rejects_tree_only_selectors() {
+    for selectors in ["li:first-child", "h2 + p", "div:has(a)"] {
+        assert!(HtmlFilter::new(HtmlParser::from_str(""), selectors).is_err(), "{}", selectors);
+    }
+}
@@ -0,0 +1,21 @@
+#!/bin/sh
+# Vendors the tokenizer and tree-construction fixtures of html5lib-tests at
+# the given revision, unmodified, and records the revision in REVISION.
+#
+#     tests/html5lib/update.sh <commit>
+set -eu
+
+revision=${1:?usage: update.sh <html5lib-tests commit>}
+directory=$(cd "$(dirname "$0")" && pwd)
+checkout=$(mktemp -d)
+trap 'rm -rf "$checkout"' EXIT
+
+git clone --quiet https://github.com/html5lib/html5lib-tests.git "$checkout"
+git -C "$checkout" checkout --quiet "$revision"
+
+rm -f "$directory"/tokenizer/*.test "$directory"/tree-construction/*.dat
+cp "$checkout"/tokenizer/*.test "$directory"/tokenizer/
+cp "$checkout"/tree-construction/*.dat "$directory"/tree-construction/
+git -C "$checkout" rev-parse HEAD > "$directory"/REVISION
+
+echo "Vendored html5lib-tests $(cat "$directory"/REVISION), run the html5lib test and update failures.txt"
@@ -0,0 +1,467 @@
+//! Runs fixtures in the html5lib-tests formats from `tests/html5lib`, the
+//! tokenizer `.test` files against the plain event stream and the
+//! tree-construction `.dat` files against a `Document` built in tree
+//! construction mode. `tests/html5lib/update.sh <commit>` vendors the
+//! upstream files unmodified and pins the commit in `tests/html5lib/REVISION`;
+//! until it is run the directories hold cases transcribed from upstream
+//! `test1.test`, `test3.test`, `numericEntities.test`, `tests1.dat`,
+//! `tables01.dat` and `template.dat`, without their error lists. Tokenizer
+//! cases starting in another state than the data state, document fragments
+//! and cases with scripting enabled are skipped.
+//!
+//! Every case is reported as passed or failed, run with `--nocapture` to see
+//! them. Cases known to fail are listed in `tests/html5lib/failures.txt`; the
+//! test fails when a case outside of the list fails, and when a listed case
+//! passes so that the list keeps tracking conformance.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::dom::{Document, NodeData, NodeId};
+use htmlparser::element::Namespace;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>)
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(map) => map.get(key),
+            _ => None
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None
+        }
+    }
+
+    fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(array) => array,
+            _ => &[]
+        }
+    }
+}
+
+/// Minimal JSON reader, enough for the tokenizer fixtures.
+struct JsonReader<'s> {
+    chars: std::iter::Peekable<std::str::Chars<'s>>
+}
+
+impl JsonReader<'_> {
+    fn parse(source: &str) -> Json {
+        let mut reader = JsonReader { chars: source.chars().peekable() };
+        let value = reader.value();
+        reader.skip_whitespace();
+        assert!(reader.chars.next().is_none(), "trailing characters after JSON value");
+
+        value
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|char| char.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) {
+        self.skip_whitespace();
+        assert_eq!(self.chars.next(), Some(expected));
+    }
+
+    fn value(&mut self) -> Json {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('{') => {
+                self.chars.next();
+                let mut map = BTreeMap::new();
+                self.skip_whitespace();
+                if self.chars.next_if_eq(&'}').is_some() {
+                    return Json::Object(map);
+                }
+
+                loop {
+                    self.skip_whitespace();
+                    let Json::String(key) = self.value() else { panic!("object key is not a string") };
+                    self.expect(':');
+                    map.insert(key, self.value());
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some(',') => continue,
+                        Some('}') => return Json::Object(map),
+                        other => panic!("unexpected {:?} in object", other)
+                    }
+                }
+            }
+
+            Some('[') => {
+                self.chars.next();
+                let mut array = vec![];
+                self.skip_whitespace();
+                if self.chars.next_if_eq(&']').is_some() {
+                    return Json::Array(array);
+                }
+
+                loop {
+                    array.push(self.value());
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some(',') => continue,
+                        Some(']') => return Json::Array(array),
+                        other => panic!("unexpected {:?} in array", other)
+                    }
+                }
+            }
+
+            Some('"') => {
+                self.chars.next();
+                Json::String(self.string())
+            }
+
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            _ => {
+                let mut number = String::new();
+                while let Some(char) = self.chars.next_if(|char| matches!(char, '0'..='9' | '-' | '+' | '.' | 'e' | 'E')) {
+                    number.push(char);
+                }
+
+                Json::Number(number.parse().expect("invalid JSON number"))
+            }
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Json {
+        for expected in keyword.chars() {
+            assert_eq!(self.chars.next(), Some(expected));
+        }
+
+        value
+    }
+
+    fn string(&mut self) -> String {
+        let mut string = String::new();
+        let mut high_surrogate: Option<u32> = None;
+        loop {
+            let char = self.chars.next().expect("unterminated JSON string");
+            let code = match char {
+                '"' => return string,
+                '\\' => match self.chars.next() {
+                    Some('u') => {
+                        let hex: String = (0..4).map(|_| self.chars.next().unwrap()).collect();
+                        u32::from_str_radix(&hex, 16).unwrap()
+                    }
+
+                    Some('n') => '\n' as u32,
+                    Some('t') => '\t' as u32,
+                    Some('r') => '\r' as u32,
+                    Some('b') => 0x08,
+                    Some('f') => 0x0c,
+                    Some(other) => other as u32,
+                    None => panic!("unterminated JSON escape")
+                },
+                char => char as u32
+            };
+
+            if let Some(high) = high_surrogate.take() {
+                if (0xdc00..0xe000).contains(&code) {
+                    string.push(char::from_u32(0x10000 + ((high - 0xd800) << 10) + (code - 0xdc00)).unwrap());
+                    continue;
+                }
+
+                string.push('\u{fffd}');
+            }
+
+            if (0xd800..0xdc00).contains(&code) {
+                high_surrogate = Some(code);
+            } else {
+                string.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    failures: Vec<String>,
+    passed: usize,
+    skipped: usize
+}
+
+impl Report {
+    fn record(&mut self, id: String, passed: bool) {
+        println!("{} {}", if passed { "PASS" } else { "FAIL" }, id);
+        if passed {
+            self.passed += 1;
+        } else {
+            self.failures.push(id);
+        }
+    }
+}
+
+fn fixtures(directory: &str, extension: &str) -> Vec<(String, String)> {
+    let mut files: Vec<(String, String)> = fs::read_dir(format!("tests/html5lib/{}", directory))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|found| found == extension))
+        .map(|path| {
+            let name = format!("{}/{}", directory, path.file_name().unwrap().to_str().unwrap());
+            (name, fs::read_to_string(path).unwrap())
+        })
+        .collect();
+
+    files.sort();
+    files
+}
+
+fn tokens(input: &str) -> Vec<Json> {
+    let mut tokens: Vec<Json> = vec![];
+    for event in HtmlParser::from_str(input) {
+        let token = match event.unwrap() {
+            HtmlEvent::Doctype { name, public_id, system_id, force_quirks, .. } => {
+                let string = |value: Option<std::borrow::Cow<str>>| value.map_or(Json::Null, |value| Json::String(value.into_owned()));
+
+                vec![Json::String("DOCTYPE".into()), string(name), string(public_id), string(system_id), Json::Bool(!force_quirks)]
+            }
+
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                let attributes = opened_element.attributes
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), Json::String(value.to_string())))
+                    .collect();
+
+                let mut token = vec![
+                    Json::String("StartTag".into()),
+                    Json::String(opened_element.name.to_str().into()),
+                    Json::Object(attributes)
+                ];
+
+                if opened_element.self_closing {
+                    token.push(Json::Bool(true));
+                }
+
+                token
+            }
+
+            // Self-closing foreign elements are followed by a closing event
+            // of their own, the tokenizer does not produce an end tag for them.
+            HtmlEvent::HtmlElementClosed { closed_element } if closed_element.self_closing => continue,
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                vec![Json::String("EndTag".into()), Json::String(closed_element.name.to_str().into())]
+            }
+
+            HtmlEvent::TextContent { content, .. } => {
+                if let Some(Json::Array(last)) = tokens.last_mut() {
+                    if last[0] == Json::String("Character".into()) {
+                        let Json::String(text) = &mut last[1] else { unreachable!() };
+                        text.push_str(&content);
+                        continue;
+                    }
+                }
+
+                vec![Json::String("Character".into()), Json::String(content.into_owned())]
+            }
+
+            HtmlEvent::Comment { content, .. } => vec![Json::String("Comment".into()), Json::String(content.into_owned())],
+            HtmlEvent::HtmlDocumentEnd => break
+        };
+
+        tokens.push(Json::Array(token));
+    }
+
+    tokens
+}
+
+fn run_tokenizer_tests(report: &mut Report) {
+    for (name, source) in fixtures("tokenizer", "test") {
+        let fixture = JsonReader::parse(&source);
+        let tests = fixture.get("tests").or(fixture.get("xmlViolationTests")).map_or(&[][..], Json::as_array);
+        for (index, test) in tests.iter().enumerate() {
+            let initial_states = test.get("initialStates").map_or(&[][..], Json::as_array);
+            let is_data_state = initial_states.iter().all(|state| state.as_str() == Some("Data state"));
+            if !is_data_state {
+                report.skipped += 1;
+                continue;
+            }
+
+            let mut input = test.get("input").cloned().unwrap();
+            let mut expected = test.get("output").cloned().unwrap_or(Json::Array(vec![]));
+            if test.get("doubleEscaped") == Some(&Json::Bool(true)) {
+                input = unescape(&input);
+                expected = unescape(&expected);
+            }
+
+            let id = format!("{}:{}", name, index);
+            report.record(id, tokens(input.as_str().unwrap()) == expected.as_array());
+        }
+    }
+}
+
+/// Resolves the `\\uXXXX` escapes left in the strings of `doubleEscaped`
+/// tests.
+fn unescape(value: &Json) -> Json {
+    match value {
+        Json::String(string) => {
+            let quoted = format!("{}\"", string.replace('"', "\\\""));
+
+            Json::String(JsonReader { chars: quoted.chars().peekable() }.string())
+        }
+
+        Json::Array(array) => Json::Array(array.iter().map(unescape).collect()),
+        Json::Object(object) => Json::Object(object.iter().map(|(key, value)| (key.clone(), unescape(value))).collect()),
+        value => value.clone()
+    }
+}
+
+fn serialize(document: &Document, node: NodeId, indent: usize, lines: &mut Vec<String>) {
+    let prefix = format!("| {}", "  ".repeat(indent));
+    match &document.node(node).data {
+        NodeData::Document => {}
+        NodeData::Doctype { name, public_id: None, system_id: None } => lines.push(format!("{}<!DOCTYPE {}>", prefix, name)),
+        NodeData::Doctype { name, public_id, system_id } => {
+            let public_id = public_id.as_deref().unwrap_or_default();
+            let system_id = system_id.as_deref().unwrap_or_default();
+            lines.push(format!("{}<!DOCTYPE {} \"{}\" \"{}\">", prefix, name, public_id, system_id));
+        }
+
+        NodeData::Comment(comment) => lines.push(format!("{}<!-- {} -->", prefix, comment)),
+        NodeData::Text(text) => {
+            // Adjacent text nodes are a single node in the expected trees.
+            let previous = document.node(node).previous_sibling().map(|id| &document.node(id).data);
+            if let (Some(NodeData::Text(_)), Some(last)) = (previous, lines.last_mut()) {
+                last.pop();
+                last.push_str(text);
+                last.push('"');
+            } else {
+                lines.push(format!("{}\"{}\"", prefix, text));
+            }
+        }
+
+        NodeData::Element(element) => {
+            let namespace = match element.namespace {
+                Namespace::Svg => "svg ",
+                Namespace::MathMl => "math ",
+                _ => ""
+            };
+
+            lines.push(format!("{}<{}{}>", prefix, namespace, element.name.to_str()));
+            // Namespaced attributes are written as `prefix name`.
+            let attributes: BTreeMap<String, &str> = element.attributes
+                .iter()
+                .map(|(name, value)| match element.attribute_namespace(name) {
+                    Some(Namespace::Xmlns) if name == "xmlns" => ("xmlns xmlns".to_string(), value.as_ref()),
+                    Some(_) => (name.replacen(':', " ", 1), value.as_ref()),
+                    None => (name.to_string(), value.as_ref())
+                })
+                .collect();
+
+            for (name, value) in attributes {
+                lines.push(format!("|   {}{}=\"{}\"", "  ".repeat(indent), name, value));
+            }
+        }
+    }
+
+    let mut indent = if node == document.root() { 0 } else { indent + 1 };
+    // Children of a template are written as its content fragment.
+    if document.node(node).element().is_some_and(|element| element.namespace == Namespace::Html && element.name.to_str() == "template") {
+        lines.push(format!("| {}content", "  ".repeat(indent)));
+        indent += 1;
+    }
+
+    for child in document.children(node) {
+        serialize(document, child, indent, lines);
+    }
+}
+
+/// Splits a `.dat` file into its cases, each a map from section name to
+/// section content.
+fn dat_cases(source: &str) -> Vec<BTreeMap<&str, String>> {
+    const SECTIONS: [&str; 7] = ["data", "errors", "new-errors", "document", "document-fragment", "script-on", "script-off"];
+
+    let mut cases: Vec<BTreeMap<&str, Vec<&str>>> = vec![];
+    let mut section = "";
+    for line in source.lines() {
+        if let Some(name) = line.strip_prefix('#').filter(|name| SECTIONS.contains(name)) {
+            if name == "data" {
+                cases.push(BTreeMap::new());
+            }
+
+            section = name;
+            cases.last_mut().unwrap().insert(section, vec![]);
+        } else if let Some(lines) = cases.last_mut().and_then(|case| case.get_mut(section)) {
+            lines.push(line);
+        }
+    }
+
+    // The blank line separating cases ends the last section of a case.
+    cases
+        .into_iter()
+        .map(|case| {
+            case.into_iter()
+                .map(|(name, mut lines)| {
+                    if name != "data" && lines.last() == Some(&"") {
+                        lines.pop();
+                    }
+
+                    (name, lines.join("\n"))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn run_tree_construction_tests(report: &mut Report) {
+    for (name, source) in fixtures("tree-construction", "dat") {
+        for (index, case) in dat_cases(&source).iter().enumerate() {
+            let (Some(data), Some(document)) = (case.get("data"), case.get("document")) else {
+                report.skipped += 1;
+                continue;
+            };
+
+            if case.contains_key("document-fragment") || case.contains_key("script-on") {
+                report.skipped += 1;
+                continue;
+            }
+
+            let options = HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() };
+            let parsed = Document::parse(HtmlParser::from_str_with_options(data, options)).unwrap();
+            let mut lines = vec![];
+            serialize(&parsed, parsed.root(), 0, &mut lines);
+
+            let id = format!("{}:{}", name, index);
+            report.record(id, lines.join("\n") == *document);
+        }
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut report = Report::default();
+    run_tokenizer_tests(&mut report);
+    run_tree_construction_tests(&mut report);
+    println!("html5lib: {} passed, {} failed, {} skipped", report.passed, report.failures.len(), report.skipped);
+
+    let known_failures: Vec<String> = fs::read_to_string("tests/html5lib/failures.txt")
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|id| !id.starts_with("//"))
+        .map(str::to_string)
+        .collect();
+
+    let regressions: Vec<&String> = report.failures.iter().filter(|id| !known_failures.contains(id)).collect();
+    let fixed: Vec<&String> = known_failures.iter().filter(|id| !report.failures.contains(id)).collect();
+
+    assert!(regressions.is_empty(), "cases failing unexpectedly: {:?}", regressions);
+    assert!(fixed.is_empty(), "cases passing now, remove them from failures.txt: {:?}", fixed);
+}
@@ -0,0 +1,54 @@
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlEvent};
+use htmlparser::element::HtmlElementName;
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/complex2.html").unwrap();
+    let image_count = HtmlParser::new(file)
+        .map(|event| event.unwrap())
+        .filter(|event| matches!(
+            event,
+            HtmlEvent::HtmlElementOpened { opened_element } if opened_element.name.is_element(HtmlElementName::Img)
+        ))
+        .count();
+
+    assert_eq!(image_count, 123);
+}
+
+#[test]
+fn terminates_after_document_end() {
+    let file = File::open("tests/htmls/entry.html").unwrap();
+    let mut parser = HtmlParser::new(file);
+    let events = parser.by_ref().collect::<Result<Vec<HtmlEvent>, _>>().unwrap();
+
+    assert_eq!(events.len(), 12);
+    assert!(matches!(events.last(), Some(HtmlEvent::TextContent { content, .. }) if content == "\n"));
+    assert!(Iterator::next(&mut parser).is_none());
+
+    let texts: Vec<String> = events
+        .into_iter()
+        .filter_map(|event| match event {
+            HtmlEvent::TextContent { content, .. } => Some(content.into_owned()),
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(texts, vec!["\n    ", "\n        ", "\n            text content\n        ", "\n    ", "\n", "\n"]);
+}
+
+#[test]
+fn take_while_stops_early() {
+    let file = File::open("tests/htmls/example.html").unwrap();
+    let head_elements: Vec<String> = HtmlParser::new(file)
+        .map(|event| event.unwrap())
+        .take_while(|event| !matches!(event, HtmlEvent::HtmlElementClosed { closed_element } if closed_element.name.is_element(HtmlElementName::Head)))
+        .filter_map(|event| match event {
+            HtmlEvent::HtmlElementOpened { opened_element } => Some(opened_element.name.to_str().to_string()),
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(head_elements, vec!["html", "head", "meta", "meta", "meta", "title", "link", "script"]);
+}
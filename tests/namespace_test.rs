@@ -0,0 +1,75 @@
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::element::Namespace;
+
+fn elements(source: &str, options: HtmlParserOptions) -> Vec<String> {
+    HtmlParser::from_str_with_options(source, options)
+        .filter_map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => Some(format!("{:?} {}", opened_element.namespace, opened_element.name.to_str())),
+            HtmlEvent::HtmlElementClosed { closed_element } => Some(format!("{:?} /{}", closed_element.namespace, closed_element.name.to_str())),
+            _ => None
+        })
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    let source = "<div><svg><linearGradient><stop/></lineargradient><foreignObject><p>x</p></foreignObject></svg><math><mi>y</mi><mrow/></math></div>";
+
+    assert_eq!(elements(source, HtmlParserOptions::default()), vec![
+        "Html div",
+        "Svg svg", "Svg linearGradient", "Svg stop", "Svg /stop", "Svg /linearGradient",
+        "Svg foreignObject", "Html p", "Html /p", "Svg /foreignObject", "Svg /svg",
+        "MathMl math", "MathMl mi", "MathMl /mi", "MathMl mrow", "MathMl /mrow", "MathMl /math",
+        "Html /div"
+    ]);
+}
+
+#[test]
+fn adjusts_attribute_names() {
+    let source = "<svg VIEWBOX='0 0 1 1' preserveaspectratio=none xlink:href=#a xml:lang=pl data-x=1><math definitionurl=u>";
+    let elements: Vec<_> = HtmlParser::from_str(source)
+        .filter_map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => Some(opened_element),
+            _ => None
+        })
+        .collect();
+
+    let svg = &elements[0];
+    assert_eq!(svg.attributes["viewBox"], "0 0 1 1");
+    assert_eq!(svg.attributes["preserveAspectRatio"], "none");
+    assert_eq!(svg.attribute_spans["viewBox"].name.start.offset, 5);
+    assert_eq!(svg.attribute_namespace("xlink:href"), Some(Namespace::XLink));
+    assert_eq!(svg.attribute_namespace("xml:lang"), Some(Namespace::Xml));
+    assert_eq!(svg.attribute_namespace("data-x"), None);
+
+    let math = &elements[1];
+    assert_eq!(math.attributes["definitionURL"], "u");
+    assert_eq!(Namespace::MathMl.url(), "http://www.w3.org/1998/Math/MathML");
+}
+
+#[test]
+fn keeps_html_names_lowercase() {
+    let source = "<div viewBox=1 xlink:href=a><lineargradient></lineargradient></div>";
+    let element = HtmlParser::from_str(source)
+        .find_map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => Some(opened_element),
+            _ => None
+        })
+        .unwrap();
+
+    assert!(element.attributes.contains_key("viewbox"));
+    assert_eq!(element.attribute_namespace("xlink:href"), None);
+    assert_eq!(elements(source, HtmlParserOptions::default())[1], "Html lineargradient");
+}
+
+#[test]
+fn works_in_tree_construction_mode() {
+    let options = HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() };
+    let elements = elements("<svg><clippath><p>x</svg><math><mi><mglyph/></mi></math>", options);
+
+    assert_eq!(elements[4..], [
+        "Svg svg", "Svg clipPath", "Svg /clipPath", "Svg /svg", "Html p",
+        "MathMl math", "MathMl mi", "MathMl mglyph", "MathMl /mglyph", "MathMl /mi", "MathMl /math", "Html /p",
+        "Html /body", "Html /html"
+    ]);
+}
@@ -0,0 +1,102 @@
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+
+fn events(source: &str) -> Vec<String> {
+    HtmlParser::from_str(source)
+        .map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => format!("<{}>", opened_element.name.to_str()),
+            HtmlEvent::HtmlElementClosed { closed_element } => format!("</{}>", closed_element.name.to_str()),
+            HtmlEvent::TextContent { content, .. } => content.into_owned(),
+            HtmlEvent::Comment { content, .. } => format!("<!--{}-->", content),
+            HtmlEvent::Doctype { .. } => "<!DOCTYPE>".to_string(),
+            HtmlEvent::HtmlDocumentEnd => unreachable!()
+        })
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    assert_eq!(events("<textarea><b>bold</b> &amp; </p></textarea><p>"), vec![
+        "<textarea>",
+        "<b>bold</b> & </p>",
+        "</textarea>",
+        "<p>"
+    ]);
+
+    assert_eq!(events("<title>a <!-- b --> &lt;c&gt;</title>"), vec!["<title>", "a <!-- b --> <c>", "</title>"]);
+}
+
+#[test]
+fn keeps_raw_text_undecoded() {
+    for name in ["style", "xmp", "iframe", "noembed", "noframes"] {
+        let source = format!("<{0}><div>&amp;</div></{0}>", name);
+        assert_eq!(events(&source), vec![format!("<{}>", name), "<div>&amp;</div>".to_string(), format!("</{}>", name)]);
+    }
+}
+
+#[test]
+fn ends_only_at_the_appropriate_end_tag() {
+    assert_eq!(events("<script>if (a </scripts> b) {}</script>"), vec!["<script>", "if (a </scripts> b) {}", "</script>"]);
+    assert_eq!(events("<script>x = '</div>';</SCRIPT ><p>"), vec!["<script>", "x = '</div>';", "</script>", "<p>"]);
+    assert_eq!(events("<style>a < b </ style></style>"), vec!["<style>", "a < b </ style>", "</style>"]);
+    assert_eq!(events("<title>a</title/>b"), vec!["<title>", "a", "</title>", "b"]);
+}
+
+#[test]
+fn keeps_escaped_scripts_together() {
+    assert_eq!(
+        events("<script><!-- document.write('<script src=\"a.js\"></script>'); --></script><p>"),
+        vec!["<script>", "<!-- document.write('<script src=\"a.js\"></script>'); -->", "</script>", "<p>"]
+    );
+
+    // Without the nested `<script` an escaped end tag still ends the script.
+    assert_eq!(events("<script><!-- a </script> -->"), vec!["<script>", "<!-- a ", "</script>", " -->"]);
+    assert_eq!(events("<script><!-- <scripts></script>b"), vec!["<script>", "<!-- <scripts>", "</script>", "b"]);
+
+    // `-->` leaves the escape, so the next end tag counts again.
+    assert_eq!(events("<script><!-- <script> --></script>"), vec!["<script>", "<!-- <script> -->", "</script>"]);
+    assert_eq!(events("<script><!--<script>--></script></script>"), vec!["<script>", "<!--<script>-->", "</script>", "</script>"]);
+    assert_eq!(events("<script><!-<script></script>x"), vec!["<script>", "<!-<script>", "</script>", "x"]);
+}
+
+#[test]
+fn reads_plaintext_to_the_end() {
+    assert_eq!(events("<plaintext>a</plaintext><b>&amp;"), vec!["<plaintext>", "a</plaintext><b>&amp;"]);
+}
+
+#[test]
+fn ends_unterminated_raw_text_with_the_document() {
+    assert_eq!(events("<textarea>a &amp; </text"), vec!["<textarea>", "a & </text"]);
+    assert_eq!(events("<script>a <"), vec!["<script>", "a <"]);
+}
+
+#[test]
+fn parses_foreign_elements_as_markup() {
+    assert_eq!(events("<svg><title><b>x</b></title></svg>"), vec!["<svg>", "<title>", "<b>", "x", "</b>", "</title>", "</svg>"]);
+}
+
+#[test]
+fn spans_the_raw_text() {
+    let source = "<script>a < b</script>";
+    let spans: Vec<(usize, usize)> = HtmlParser::from_str(source)
+        .filter_map(|event| match event.unwrap() {
+            HtmlEvent::TextContent { span, .. } => Some((span.start.offset, span.end.offset)),
+            HtmlEvent::HtmlElementClosed { closed_element } => Some((closed_element.span.start.offset, closed_element.span.end.offset)),
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(spans, vec![(8, 13), (13, 22)]);
+}
+
+#[test]
+fn works_in_tree_construction_mode() {
+    let options = HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() };
+    let texts: Vec<String> = HtmlParser::from_str_with_options("<textarea><p>x</textarea>", options)
+        .filter_map(|event| match event.unwrap() {
+            HtmlEvent::TextContent { content, depth, .. } => Some(format!("{} {}", depth, content)),
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(texts, vec!["3 <p>x"]);
+}
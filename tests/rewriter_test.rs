@@ -0,0 +1,171 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use htmlparser::rewriter::HtmlRewriter;
+
+fn rewrite(source: &str, configure: impl FnOnce(&mut HtmlRewriter<Vec<u8>>)) -> String {
+    let mut rewriter = HtmlRewriter::new(vec![]);
+    configure(&mut rewriter);
+    rewriter.rewrite(source.as_bytes()).unwrap();
+
+    String::from_utf8(rewriter.into_inner()).unwrap()
+}
+
+#[test]
+fn it_works() {
+    let source = "<html>\n<head>\n  <script src=\"https://tracker.example/t.js\"></script>\n  <script>var keep = 1 < 2;</script>\n</head>\n\
+        <body>\n  <a href='/about' class=nav>About &amp; us</a>\n  <a href=\"https://other.example/\">Other</a>\n</body>\n</html>\n";
+
+    let output = rewrite(source, |rewriter| {
+        rewriter.on("script[src*=tracker]", |element| element.remove()).unwrap();
+        rewriter.on("a[href^=http]", |element| {
+            element.set_attribute("rel", "noopener");
+            element.set_attribute("target", "_blank");
+        }).unwrap();
+        rewriter.on("body", |element| element.append("<footer>injected</footer>\n")).unwrap();
+    });
+
+    assert_eq!(
+        output,
+        "<html>\n<head>\n  \n  <script>var keep = 1 < 2;</script>\n</head>\n\
+        <body>\n  <a href='/about' class=nav>About &amp; us</a>\n  <a href=\"https://other.example/\" rel=\"noopener\" target=\"_blank\">Other</a>\n\
+        <footer>injected</footer>\n</body>\n</html>\n"
+    );
+}
+
+#[test]
+fn copies_unmatched_documents_exactly() {
+    for name in ["complex.html", "complex2.html", "example.html", "malformed.html"] {
+        let source = fs::read(format!("tests/htmls/{}", name)).unwrap();
+        let mut rewriter = HtmlRewriter::new(vec![]);
+        rewriter.on("blink", |element| element.remove()).unwrap();
+        rewriter.rewrite(&source[..]).unwrap();
+
+        assert!(rewriter.into_inner() == source, "{}", name);
+    }
+}
+
+#[test]
+fn inserts_and_replaces_content() {
+    let source = "<div id=a><p>one</p><br><p>two <b>bold</b></p></div>";
+
+    assert_eq!(
+        rewrite(source, |rewriter| {
+            rewriter.on("p", |element| {
+                element.before("[");
+                element.prepend("(");
+                element.append(")");
+                element.after("]");
+            }).unwrap();
+            rewriter.on("br", |element| element.after("<hr>")).unwrap();
+        }),
+        "<div id=a>[<p>(one)</p>]<br><hr>[<p>(two <b>bold</b>)</p>]</div>"
+    );
+
+    assert_eq!(
+        rewrite(source, |rewriter| rewriter.on("p", |element| element.set_inner_content("x")).unwrap()),
+        "<div id=a><p>x</p><br><p>x</p></div>"
+    );
+
+    assert_eq!(
+        rewrite(source, |rewriter| rewriter.on("div > p", |element| element.replace("<span>p</span>")).unwrap()),
+        "<div id=a><span>p</span><br><span>p</span></div>"
+    );
+
+    assert_eq!(
+        rewrite(source, |rewriter| {
+            rewriter.on("p", |element| element.remove()).unwrap();
+            rewriter.on("b", |element| element.set_attribute("class", "never")).unwrap();
+        }),
+        "<div id=a><br></div>"
+    );
+}
+
+#[test]
+fn edits_attributes() {
+    let source = "<img src=\"a.png\" data-track=\"1\" alt='say \"hi\"'><svg><use href=\"#i\"/></svg>";
+
+    assert_eq!(
+        rewrite(source, |rewriter| {
+            rewriter.on("img", |element| {
+                assert_eq!(element.attribute("alt"), Some("say \"hi\""));
+                element.remove_attribute("data-track");
+                element.set_attribute("loading", "lazy");
+            }).unwrap();
+            rewriter.on("use", |element| element.set_attribute("href", "#j")).unwrap();
+        }),
+        "<img src=\"a.png\" alt=\"say &quot;hi&quot;\" loading=\"lazy\"><svg><use href=\"#j\"/></svg>"
+    );
+}
+
+#[test]
+fn ends_elements_closed_by_ancestors() {
+    assert_eq!(
+        rewrite("<div class=ad><p>a</div><span>keep</span>", |rewriter| rewriter.on("div.ad", |element| element.remove()).unwrap()),
+        "<span>keep</span>"
+    );
+
+    assert_eq!(
+        rewrite("<ul><li class=t>a<li>b</ul><p>keep</p>", |rewriter| rewriter.on("li.t", |element| element.remove()).unwrap()),
+        "<ul></ul><p>keep</p>"
+    );
+
+    assert_eq!(
+        rewrite("<ul><li class=t>a<li>b</ul><p>keep</p>", |rewriter| {
+            rewriter.on("li.t", |element| {
+                element.append("!");
+                element.after("]");
+            }).unwrap();
+        }),
+        "<ul><li class=t>a<li>b!]</ul><p>keep</p>"
+    );
+}
+
+struct SharedWriter(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hands out the source in small chunks, noting how much output was written
+/// before the last one.
+struct ObservingReader<'a> {
+    data: &'a [u8],
+    output: Rc<RefCell<Vec<u8>>>,
+    written_before_end: Option<usize>
+}
+
+impl Read for ObservingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = 1024.min(buf.len()).min(self.data.len());
+        if length > 0 && length == self.data.len() {
+            self.written_before_end = Some(self.output.borrow().len());
+        }
+
+        buf[..length].copy_from_slice(&self.data[..length]);
+        self.data = &self.data[length..];
+        Ok(length)
+    }
+}
+
+#[test]
+fn writes_output_while_reading() {
+    let source = "<p class=x>text &amp; more</p><!-- c -->\n".repeat(5000) + "<a href=/>end</a>";
+    let output = Rc::new(RefCell::new(vec![]));
+    let mut reader = ObservingReader { data: source.as_bytes(), output: output.clone(), written_before_end: None };
+
+    let mut rewriter = HtmlRewriter::new(SharedWriter(output.clone()));
+    rewriter.on("a", |element| element.set_attribute("rel", "nofollow")).unwrap();
+    rewriter.rewrite(&mut reader).unwrap();
+
+    assert!(reader.written_before_end.unwrap() > source.len() - 2048);
+    assert_eq!(*output.borrow(), source.replace("<a href=/>", "<a href=\"/\" rel=\"nofollow\">").into_bytes());
+}
@@ -0,0 +1,92 @@
+use std::fs;
+
+use htmlparser::dom::{Document, NodeId};
+use htmlparser::selector::Selector;
+
+fn texts(document: &Document, nodes: Vec<NodeId>) -> Vec<String> {
+    nodes.into_iter().map(|node| document.text_content(node)).collect()
+}
+
+fn select(source: &str, selector: &str) -> Vec<String> {
+    let document = Document::from_str(source).unwrap();
+    let nodes = document.select(selector).unwrap();
+
+    texts(&document, nodes)
+}
+
+#[test]
+fn it_works() {
+    let source = fs::read_to_string("tests/htmls/selectors.html").unwrap();
+
+    assert_eq!(select(&source, "div.card > a[href^='https']"), vec!["Read"]);
+    assert_eq!(select(&source, "#menu a.external"), vec!["Blog", "Old"]);
+    assert_eq!(select(&source, "nav > a:not(.legacy)"), vec!["Home", "Blog"]);
+    assert_eq!(select(&source, "h2, .featured p"), vec!["First", "Intro", "Second"]);
+    assert_eq!(select(&source, "div:has(> a) h2"), vec!["First"]);
+    assert_eq!(select(&source, "div:has(span + p) p:first-of-type"), vec!["Body"]);
+    assert_eq!(select(&source, ".card p:last-child"), vec!["More"]);
+    assert_eq!(select(&source, "h2 + p"), vec!["Intro"]);
+    assert_eq!(select(&source, "h2 ~ p"), vec!["Intro", "Body", "More"]);
+    assert_eq!(select(&source, "span:empty ~ *"), vec!["Body", "More"]);
+}
+
+#[test]
+fn matches_attribute_operators() {
+    let source = fs::read_to_string("tests/htmls/selectors.html").unwrap();
+
+    assert_eq!(select(&source, "[data-lang|=en] h2"), vec!["First"]);
+    assert_eq!(select(&source, "[data-lang=PL i] h2"), vec!["Second"]);
+    assert_eq!(select(&source, "[data-lang=PL] h2"), Vec::<String>::new());
+    assert_eq!(select(&source, "a[class~=legacy]"), vec!["Old"]);
+    assert_eq!(select(&source, "a[href$=\"/old\"]"), vec!["Old"]);
+    assert_eq!(select(&source, "a[href*=example][class]"), vec!["Blog", "Old"]);
+}
+
+#[test]
+fn matches_nth_child() {
+    let source = fs::read_to_string("tests/htmls/selectors.html").unwrap();
+
+    assert_eq!(select(&source, "li:nth-child(odd)"), vec!["1", "3", "5"]);
+    assert_eq!(select(&source, "li:nth-child(2n)"), vec!["2", "4"]);
+    assert_eq!(select(&source, "li:nth-child(-n + 2)"), vec!["1", "2"]);
+    assert_eq!(select(&source, "li:nth-last-child(2)"), vec!["4"]);
+    assert_eq!(select(&source, "li:nth-of-type(3n+1)"), vec!["1", "4"]);
+    assert_eq!(select(&source, ":root > body > ul > li:first-child"), vec!["1"]);
+}
+
+#[test]
+fn reports_invalid_selectors() {
+    let error = Selector::parse("div > > a").unwrap_err();
+    assert_eq!(error.message, "expected a selector");
+    assert_eq!(error.offset, 6);
+
+    assert_eq!(Selector::parse("a:hover").unwrap_err().message, "unsupported pseudo-class");
+    assert_eq!(Selector::parse("a[href=\"x]").unwrap_err().message, "unterminated string");
+    assert_eq!(Selector::parse("li:nth-child(x)").unwrap_err().message, "invalid nth argument");
+}
+
+#[test]
+fn replaces_ad_hoc_class_matching() {
+    let source = fs::read_to_string("tests/htmls/complex.html").unwrap();
+    let document = Document::from_str(&source).unwrap();
+    let selector = Selector::parse("a[href^='http']").unwrap();
+    let links: Vec<String> = selector
+        .select(&document, document.root())
+        .into_iter()
+        .map(|node| document.node(node).element().unwrap().attributes["href"].to_string())
+        .collect();
+
+    assert!(links.contains(&"https://www.facebook.com/EpicDramaPL/".to_string()));
+    assert!(links.iter().all(|href| href.starts_with("http")));
+}
+
+#[test]
+fn matches_svg_names_case_insensitively() {
+    let source = "<svg viewBox='0 0 1 1'><linearGradient id=g><stop/></linearGradient><text>a</text></svg><lineargradient viewbox=1>b</lineargradient>";
+
+    assert_eq!(select(source, "svg[viewBox] text"), vec!["a"]);
+    assert_eq!(select(source, "svg[viewbox='0 0 1 1'] > text"), vec!["a"]);
+    assert_eq!(select(source, "linearGradient#g").len(), 1);
+    assert_eq!(select(source, "svg lineargradient").len(), 1);
+    assert_eq!(select(source, "lineargradient[viewBox]"), vec!["b"]);
+}
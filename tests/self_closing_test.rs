@@ -0,0 +1,54 @@
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlEvent};
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/self_closing.html").unwrap();
+    let mut parser = HtmlParser::new(file);
+    let mut events = vec![];
+
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                let self_closing = if opened_element.self_closing { " /" } else { "" };
+                events.push(format!("<{}{}>", opened_element.name.to_str(), self_closing));
+
+                if opened_element.name.to_str() == "a" {
+                    assert_eq!(opened_element.attributes.get("href"), Some(&"foo/".into()));
+                }
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                events.push(format!("</{}>", closed_element.name.to_str()));
+            }
+
+            HtmlEvent::HtmlDocumentEnd => {
+                break;
+            }
+
+            _ => {
+                continue;
+            }
+        }
+    }
+
+    assert_eq!(events, vec![
+        "<div>",
+        "<img />",
+        "<br />",
+        "<custom-el />",
+        "<svg>",
+        "<path />",
+        "</path>",
+        "<circle>",
+        "</circle>",
+        "<foreignObject>",
+        "<p />",
+        "</foreignObject>",
+        "</svg>",
+        "<a>",
+        "</a>",
+        "</div>",
+    ]);
+}
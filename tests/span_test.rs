@@ -0,0 +1,95 @@
+use std::fs::File;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::position::Span;
+
+fn offsets(span: Span) -> (usize, usize) {
+    (span.start.offset, span.end.offset)
+}
+
+#[test]
+fn it_works() {
+    let file = File::open("tests/htmls/spans.html").unwrap();
+    let events: Vec<HtmlEvent> = HtmlParser::new(file).map(|event| event.unwrap()).collect();
+
+    let HtmlEvent::HtmlElementOpened { opened_element: div } = &events[0] else { panic!() };
+    assert_eq!(offsets(div.span), (0, 15));
+    assert_eq!(offsets(div.attribute_spans["id"].name), (5, 7));
+    assert_eq!(offsets(div.attribute_spans["id"].value.unwrap()), (9, 13));
+
+    let HtmlEvent::TextContent { content, span, .. } = &events[1] else { panic!() };
+    assert_eq!(content, "\n  ");
+    assert_eq!(offsets(*span), (15, 18));
+
+    let HtmlEvent::HtmlElementOpened { opened_element: p } = &events[2] else { panic!() };
+    assert_eq!(offsets(p.span), (18, 40));
+    assert_eq!((p.span.start.line, p.span.start.column), (2, 3));
+    assert_eq!(offsets(p.attribute_spans["class"].name), (21, 26));
+    assert_eq!(offsets(p.attribute_spans["class"].value.unwrap()), (27, 32));
+    assert_eq!(offsets(p.attribute_spans["hidden"].name), (33, 39));
+    assert_eq!(p.attribute_spans["hidden"].value, None);
+
+    let HtmlEvent::TextContent { content, span, .. } = &events[3] else { panic!() };
+    assert_eq!(content, "Hello & bye");
+    assert_eq!(offsets(*span), (40, 55));
+
+    let HtmlEvent::HtmlElementClosed { closed_element } = &events[4] else { panic!() };
+    assert_eq!(offsets(closed_element.span), (55, 59));
+
+    let HtmlEvent::Comment { content, span, .. } = &events[6] else { panic!() };
+    assert_eq!(content, " note ");
+    assert_eq!(offsets(*span), (62, 75));
+    assert_eq!((span.start.line, span.start.column, span.end.column), (3, 3, 16));
+
+    let HtmlEvent::TextContent { span, .. } = &events[7] else { panic!() };
+    assert_eq!(offsets(*span), (75, 76));
+
+    let HtmlEvent::HtmlElementClosed { closed_element } = &events[8] else { panic!() };
+    assert_eq!(offsets(closed_element.span), (76, 82));
+    assert_eq!((closed_element.span.start.line, closed_element.span.start.column), (4, 1));
+}
+
+#[test]
+fn script_text_span() {
+    let file = File::open("tests/htmls/example.html").unwrap();
+    let source = std::fs::read_to_string("tests/htmls/example.html").unwrap();
+    let script_text = HtmlParser::new(file)
+        .map(|event| event.unwrap())
+        .find_map(|event| match event {
+            HtmlEvent::TextContent { content, span, .. } if content.starts_with("if") => Some((content, span)),
+            _ => None
+        })
+        .unwrap();
+
+    assert_eq!(&source[script_text.1.start.offset..script_text.1.end.offset], script_text.0);
+}
+
+#[test]
+fn text_spans_cover_content() {
+    const PIECES: [&str; 22] = [
+        "<", ">", "/", "!", "?", "-", "&", "#", ";", "=", "\"", "'", " ", "\n", "a", "é", "amp", "svg", "div", "title", "script", "<!--"
+    ];
+
+    let events: Vec<HtmlEvent> = HtmlParser::from_str("<&a>svg</p>").map(|event| event.unwrap()).collect();
+    let HtmlEvent::TextContent { content, span, .. } = &events[0] else { panic!() };
+    assert_eq!(content, "<&a>svg");
+    assert_eq!(offsets(*span), (0, 7));
+
+    // Random documents from a linear congruential generator, so that failures
+    // can be reproduced.
+    let mut state: u64 = 1;
+    for _ in 0..2000 {
+        let mut source = String::new();
+        for _ in 0..24 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            source.push_str(PIECES[(state >> 33) as usize % PIECES.len()]);
+        }
+
+        let options = HtmlParserOptions { decode_entities: false, ..HtmlParserOptions::default() };
+        for event in HtmlParser::from_str_with_options(&source, options) {
+            if let HtmlEvent::TextContent { content, span, .. } = event.unwrap() {
+                assert_eq!(&source[span.start.offset..span.end.offset], content, "in {:?}", source);
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use htmlparser::{HtmlParser, HtmlEvent};
+
+fn events(source: &str) -> Vec<String> {
+    HtmlParser::from_str(source)
+        .map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => format!("<{}> {}", opened_element.name.to_str(), opened_element.depth),
+            HtmlEvent::HtmlElementClosed { closed_element } => format!("</{}> {}", closed_element.name.to_str(), closed_element.depth),
+            HtmlEvent::TextContent { content, .. } => format!("{:?}", content),
+            HtmlEvent::Comment { content, .. } => format!("<!--{}-->", content),
+            HtmlEvent::Doctype { .. } => "<!DOCTYPE>".to_string(),
+            HtmlEvent::HtmlDocumentEnd => unreachable!()
+        })
+        .collect()
+}
+
+#[test]
+fn it_works() {
+    assert_eq!(events("a < b"), vec!["\"a < b\""]);
+    assert_eq!(events("<p>a <= b</p>"), vec!["<p> 0", "\"a <= b\"", "</p> 0"]);
+}
+
+#[test]
+fn keeps_end_tags_behind_text() {
+    assert_eq!(events("<div>1 <2</div><span>"), vec!["<div> 0", "\"1 <2\"", "</div> 0", "<span> 0"]);
+    assert_eq!(events("if a < b <p>x</p>"), vec!["\"if a < b \"", "<p> 0", "\"x\"", "</p> 0"]);
+    assert_eq!(events("a<<b>"), vec!["\"a<\"", "<b> 0"]);
+}
+
+#[test]
+fn keeps_less_than_signs_at_the_end() {
+    assert_eq!(events("<"), vec!["\"<\""]);
+    assert_eq!(events("</"), vec!["\"</\""]);
+    assert_eq!(events("a <"), vec!["\"a <\""]);
+    assert_eq!(events("a </"), vec!["\"a </\""]);
+}
+
+#[test]
+fn handles_invalid_tag_names() {
+    assert_eq!(events("<#>"), vec!["\"<#>\""]);
+    assert_eq!(events("a</>b"), vec!["\"a\"", "\"b\""]);
+    assert_eq!(events("</#>x"), vec!["<!--#-->", "\"x\""]);
+    assert_eq!(events("</ p>"), vec!["<!-- p-->"]);
+}
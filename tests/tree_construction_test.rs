@@ -0,0 +1,286 @@
+use std::fs;
+use std::io::{self, Read};
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::dom::Document;
+
+fn options() -> HtmlParserOptions {
+    HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() }
+}
+
+/// Serializes the events as an indented tree, one node per line.
+fn tree(source: &str) -> Vec<String> {
+    let mut lines = vec![];
+    for event in HtmlParser::from_str_with_options(source, options()) {
+        match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                lines.push(format!("{}<{}>", "  ".repeat(opened_element.depth), opened_element.name.to_str()));
+            }
+
+            HtmlEvent::TextContent { content, depth, .. } => lines.push(format!("{}\"{}\"", "  ".repeat(depth), content)),
+            HtmlEvent::Comment { content, depth, .. } => lines.push(format!("{}<!-- {} -->", "  ".repeat(depth), content)),
+            HtmlEvent::Doctype { name, .. } => lines.push(format!("<!DOCTYPE {}>", name.unwrap_or_default())),
+            _ => {}
+        }
+    }
+
+    lines
+}
+
+/// Checks that every opened element is closed exactly once, innermost first,
+/// and that the depths and the ancestors reported by the parser follow the
+/// nesting.
+fn assert_well_formed(source: &str) {
+    let mut open: Vec<String> = vec![];
+    let mut parser = HtmlParser::from_str_with_options(source, options());
+    loop {
+        let event = parser.next().unwrap();
+        if matches!(event, HtmlEvent::HtmlDocumentEnd) {
+            break;
+        }
+
+        match event {
+            HtmlEvent::HtmlElementOpened { opened_element } => {
+                assert_eq!(opened_element.depth, open.len());
+                if !opened_element.name.is_self_closing_element() {
+                    open.push(opened_element.name.to_str().to_string());
+                }
+            }
+
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                assert_eq!(open.pop().as_deref(), Some(closed_element.name.to_str()));
+                assert_eq!(closed_element.depth, open.len());
+            }
+
+            HtmlEvent::TextContent { depth, .. } | HtmlEvent::Comment { depth, .. } => assert_eq!(depth, open.len()),
+            HtmlEvent::Doctype { .. } | HtmlEvent::HtmlDocumentEnd => {}
+        }
+
+        let ancestors: Vec<&str> = parser.ancestors().iter().map(|element| element.name.to_str()).collect();
+        assert_eq!(ancestors, open, "in {:?}", source);
+    }
+
+    assert!(open.is_empty(), "left open: {:?}", open);
+}
+
+#[test]
+fn it_works() {
+    assert_eq!(tree("<!DOCTYPE html><title>Hi</title><p>One<p>Two<hr>"), vec![
+        "<!DOCTYPE html>",
+        "<html>",
+        "  <head>",
+        "    <title>",
+        "      \"Hi\"",
+        "  <body>",
+        "    <p>",
+        "      \"One\"",
+        "    <p>",
+        "      \"Two\"",
+        "    <hr>"
+    ]);
+}
+
+#[test]
+fn implies_end_tags_of_list_items_and_cells() {
+    assert_eq!(tree("<ul><li>a<li>b</ul><dl><dt>t<dd>d</dl><hr>")[3..], [
+        "    <ul>",
+        "      <li>",
+        "        \"a\"",
+        "      <li>",
+        "        \"b\"",
+        "    <dl>",
+        "      <dt>",
+        "        \"t\"",
+        "      <dd>",
+        "        \"d\"",
+        "    <hr>"
+    ]);
+
+    assert_eq!(tree("<table><tr><td>a<td>b<tr><th>c</table><hr>")[3..], [
+        "    <table>",
+        "      <tbody>",
+        "        <tr>",
+        "          <td>",
+        "            \"a\"",
+        "          <td>",
+        "            \"b\"",
+        "        <tr>",
+        "          <th>",
+        "            \"c\"",
+        "    <hr>"
+    ]);
+}
+
+#[test]
+fn runs_the_adoption_agency_algorithm() {
+    assert_eq!(tree("<p><b><i>a</b>b</i>c</p><hr>")[3..], [
+        "    <p>",
+        "      <b>",
+        "        <i>",
+        "          \"a\"",
+        "      <i>",
+        "        \"b\"",
+        "      \"c\"",
+        "    <hr>"
+    ]);
+
+    // Content before the misnested end tag stays where it was emitted.
+    assert_eq!(tree("<b>1<div>2</b>3</div><hr>")[3..], [
+        "    <b>",
+        "      \"1\"",
+        "      <div>",
+        "        \"2\"",
+        "    <div>",
+        "      \"3\"",
+        "    <hr>"
+    ]);
+
+    assert_eq!(tree("<a href=1>x<a href=2>y</a><hr>")[3..], [
+        "    <a>",
+        "      \"x\"",
+        "    <a>",
+        "      \"y\"",
+        "    <hr>"
+    ]);
+}
+
+#[test]
+fn foster_parents_content_out_of_tables() {
+    assert_eq!(tree("<div><table>x<span>y</span><tr><td>z</td></tr>w</table></div><hr>")[3..], [
+        "    <div>",
+        "      \"x\"",
+        "      <span>",
+        "        \"y\"",
+        "      \"w\"",
+        "      <table>",
+        "        <tbody>",
+        "          <tr>",
+        "            <td>",
+        "              \"z\"",
+        "    <hr>"
+    ]);
+}
+
+#[test]
+fn closes_foreign_content() {
+    assert_eq!(tree("<svg><circle/><g><rect></svg><p>x</p><math><mi><b>y</b></mi></math><hr>")[3..], [
+        "    <svg>",
+        "      <circle>",
+        "      <g>",
+        "        <rect>",
+        "    <p>",
+        "      \"x\"",
+        "    <math>",
+        "      <mi>",
+        "        <b>",
+        "          \"y\"",
+        "    <hr>"
+    ]);
+}
+
+#[test]
+fn emits_synthetic_close_events() {
+    let options = options();
+    let events: Vec<(String, bool)> = HtmlParser::from_str_with_options("<div><span>a</div>", options)
+        .filter_map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementClosed { closed_element } => {
+                Some((closed_element.name.to_str().to_string(), closed_element.span.start == closed_element.span.end))
+            }
+
+            _ => None
+        })
+        .collect();
+
+    assert_eq!(events, vec![
+        ("head".to_string(), true),
+        ("span".to_string(), true),
+        ("div".to_string(), false),
+        ("body".to_string(), true),
+        ("html".to_string(), true)
+    ]);
+}
+
+#[test]
+fn drops_stray_end_tags() {
+    assert_eq!(tree("</div><p>a</span></p></p><hr>")[3..], [
+        "    <p>",
+        "      \"a\"",
+        "    <p>",
+        "    <hr>"
+    ]);
+}
+
+#[test]
+fn produces_well_formed_trees() {
+    for source in [
+        "<b><i></b></i>",
+        "<table><td><table><div>x<table><tr>y</table></div></table>z</table>",
+        "<select><option>a<option>b<optgroup><option>c</select>",
+        "<a><p><a>x</p></a>",
+        "<table><caption>c<tr><td><b>d</table><i>e",
+        "<p><svg><foreignObject><p>x</svg>y",
+        "<ruby>a<rt>b<rp>c</ruby>",
+        "<table><template><tr><td>x</template>y</table><template><col>",
+        "<frameset><frame><frameset><noframes>x</frameset>"
+    ] {
+        assert_well_formed(source);
+    }
+
+    for path in fs::read_dir("tests/htmls").unwrap() {
+        let source = fs::read_to_string(path.unwrap().path()).unwrap();
+        assert_well_formed(&source);
+    }
+}
+
+#[test]
+fn builds_documents() {
+    let source = "<table><tr><td>a</table>";
+    let document = Document::parse(HtmlParser::from_str_with_options(source, options())).unwrap();
+    let cells = document.select("html > body > table > tbody > tr > td").unwrap();
+
+    assert_eq!(cells.len(), 1);
+    assert_eq!(document.text_content(cells[0]), "a");
+}
+
+#[test]
+fn emits_events_in_front_of_open_tables() {
+    // The reader fails inside of the table, everything in front of it has
+    // been emitted by then.
+    let source = "<p>a</p><table><tr><td>b".as_bytes().chain(FailingReader);
+    let mut parser = HtmlParser::with_options(source, options());
+    let mut names = vec![];
+    while let Ok(event) = parser.next() {
+        if let HtmlEvent::HtmlElementOpened { opened_element } = event {
+            names.push(opened_element.name.to_str().to_string());
+        }
+    }
+
+    assert_eq!(names, vec!["html", "head", "body", "p"]);
+}
+
+#[test]
+fn reports_ancestors_of_the_returned_event() {
+    let mut parser = HtmlParser::from_str_with_options("<table><tr><td>x</table><p>y", options());
+    let mut paths = vec![];
+    loop {
+        match parser.next().unwrap() {
+            HtmlEvent::TextContent { depth, .. } => {
+                assert_eq!(parser.depth(), depth);
+                paths.push(parser.path());
+            }
+
+            HtmlEvent::HtmlDocumentEnd => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(paths, vec!["html > body > table > tbody > tr > td", "html > body > p"]);
+}
+
+struct FailingReader;
+
+impl Read for FailingReader {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        Err(io::Error::other("connection reset"))
+    }
+}
@@ -0,0 +1,76 @@
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent, WhitespaceMode};
+use htmlparser::doctype::QuirksMode;
+
+fn events(source: &str, options: HtmlParserOptions) -> Vec<String> {
+    HtmlParser::from_str_with_options(source, options)
+        .map(|event| match event.unwrap() {
+            HtmlEvent::HtmlElementOpened { opened_element } => format!("<{}>", opened_element.name.to_str()),
+            HtmlEvent::HtmlElementClosed { closed_element } => format!("</{}>", closed_element.name.to_str()),
+            HtmlEvent::TextContent { content, depth, .. } => format!("{} {:?}", depth, content),
+            HtmlEvent::Comment { content, .. } => format!("<!--{}-->", content),
+            HtmlEvent::Doctype { .. } => "<!DOCTYPE>".to_string(),
+            HtmlEvent::HtmlDocumentEnd => unreachable!()
+        })
+        .collect()
+}
+
+fn with_whitespace(whitespace: WhitespaceMode) -> HtmlParserOptions {
+    HtmlParserOptions { whitespace, ..HtmlParserOptions::default() }
+}
+
+#[test]
+fn it_works() {
+    let source = "<p> <b>a</b> <i>b\n\tc</i>\n</p>";
+
+    assert_eq!(events(source, HtmlParserOptions::default()), vec![
+        "<p>", "1 \" \"", "<b>", "2 \"a\"", "</b>", "1 \" \"", "<i>", "2 \"b\\n\\tc\"", "</i>", "1 \"\\n\"", "</p>"
+    ]);
+    assert_eq!(events(source, with_whitespace(WhitespaceMode::Collapse)), vec![
+        "<p>", "1 \" \"", "<b>", "2 \"a\"", "</b>", "1 \" \"", "<i>", "2 \"b c\"", "</i>", "1 \" \"", "</p>"
+    ]);
+    assert_eq!(events(source, with_whitespace(WhitespaceMode::Trim)), vec![
+        "<p>", "<b>", "2 \"a\"", "</b>", "<i>", "2 \"b c\"", "</i>", "</p>"
+    ]);
+}
+
+#[test]
+fn always_keeps_raw_text() {
+    let source = "<textarea>\n  a  b\n</textarea><script>\n  // x\n  y();\n</script>";
+
+    assert_eq!(events(source, with_whitespace(WhitespaceMode::Trim)), vec![
+        "<textarea>", "1 \"\\n  a  b\\n\"", "</textarea>", "<script>", "1 \"\\n  // x\\n  y();\\n\"", "</script>"
+    ]);
+}
+
+#[test]
+fn spans_the_whole_text() {
+    let source = "<pre>\n  a  </pre>";
+    let spans = |options| -> Vec<(usize, usize)> {
+        HtmlParser::from_str_with_options(source, options)
+            .filter_map(|event| match event.unwrap() {
+                HtmlEvent::TextContent { span, .. } => Some((span.start.offset, span.end.offset)),
+                _ => None
+            })
+            .collect()
+    };
+
+    assert_eq!(spans(HtmlParserOptions::default()), vec![(5, 11)]);
+    assert_eq!(spans(with_whitespace(WhitespaceMode::Collapse)), vec![(5, 11)]);
+    assert_eq!(spans(with_whitespace(WhitespaceMode::Trim)), vec![(8, 9)]);
+}
+
+#[test]
+fn places_whitespace_in_the_tree() {
+    let source = "<!DOCTYPE html>\n<html>\n<head>\n<title>x</title>\n</head>\n<body>\n<pre>\nline\n</pre>\n<table> <tr>\n<td>a</td> </tr>x</table>\n</body>\n</html>\n";
+    let options = HtmlParserOptions { tree_construction: true, ..HtmlParserOptions::default() };
+    let mut parser = HtmlParser::from_str_with_options(source, options.clone());
+    while !matches!(parser.next().unwrap(), HtmlEvent::HtmlDocumentEnd) {}
+
+    assert_eq!(parser.quirks_mode(), QuirksMode::NoQuirks);
+    assert_eq!(events(source, options), vec![
+        "<!DOCTYPE>", "<html>", "<head>", "2 \"\\n\"", "<title>", "3 \"x\"", "</title>", "2 \"\\n\"", "</head>", "1 \"\\n\"",
+        "<body>", "2 \"\\n\"", "<pre>", "3 \"line\\n\"", "</pre>", "2 \"\\n\"", "2 \"x\"",
+        "<table>", "3 \" \"", "<tbody>", "<tr>", "5 \"\\n\"", "<td>", "6 \"a\"", "</td>", "5 \" \"", "</tr>", "</tbody>", "</table>",
+        "2 \"\\n\"", "2 \"\\n\"", "2 \"\\n\"", "</body>", "</html>"
+    ]);
+}
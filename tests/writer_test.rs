@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+use std::fs;
+
+use htmlparser::{HtmlParser, HtmlParserOptions, HtmlEvent};
+use htmlparser::position::Span;
+use htmlparser::dom::Document;
+use htmlparser::writer::HtmlWriter;
+
+fn describe(event: HtmlEvent) -> String {
+    match event {
+        HtmlEvent::HtmlElementOpened { opened_element: element } | HtmlEvent::HtmlElementClosed { closed_element: element } => {
+            let mut attributes: Vec<_> = element.attributes.into_iter().collect();
+            attributes.sort();
+
+            format!("{} {:?} {}", element.name.to_str(), attributes, element.self_closing)
+        }
+
+        HtmlEvent::TextContent { content, .. } => format!("text {}", content),
+        HtmlEvent::Comment { content, .. } => format!("comment {}", content),
+        HtmlEvent::Doctype { name, public_id, system_id, .. } => format!("doctype {:?} {:?} {:?}", name, public_id, system_id),
+        HtmlEvent::HtmlDocumentEnd => "end".to_string()
+    }
+}
+
+fn serialize(source: &str) -> String {
+    serialize_with_options(source, HtmlParserOptions::default())
+}
+
+fn serialize_with_options(source: &str, options: HtmlParserOptions) -> String {
+    let mut writer = HtmlWriter::with_decode_entities(vec![], options.decode_entities);
+    for event in HtmlParser::from_str_with_options(source, options) {
+        writer.write_event(&event.unwrap()).unwrap();
+    }
+
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+#[test]
+fn it_works() {
+    let source = "<!DOCTYPE html><p class=\"a b\" title='say \"hi\"' hidden>Fish &amp; Chips &lt;3<br></p>\
+        <script>if (a < b && c) {}</script><!-- note --><svg><circle r=\"1\"/></svg>";
+
+    assert_eq!(
+        serialize(source),
+        "<!DOCTYPE html><p class=\"a b\" title=\"say &quot;hi&quot;\" hidden=\"\">Fish &amp; Chips &lt;3<br></p>\
+        <script>if (a < b && c) {}</script><!-- note --><svg><circle r=\"1\"/></svg>"
+    );
+}
+
+#[test]
+fn round_trips() {
+    for name in ["example.html", "complex.html", "complex2.html", "entities.html", "attributes.html", "comments.html", "self_closing.html"] {
+        let source = fs::read_to_string(format!("tests/htmls/{}", name)).unwrap();
+        let serialized = serialize(&source);
+
+        let expected: Vec<String> = HtmlParser::from_str(&source).map(|event| describe(event.unwrap())).collect();
+        let actual: Vec<String> = HtmlParser::from_str(&serialized).map(|event| describe(event.unwrap())).collect();
+        assert_eq!(actual, expected, "{}", name);
+        assert_eq!(serialize(&serialized), serialized, "{}", name);
+    }
+}
+
+#[test]
+fn round_trips_undecoded_text() {
+    let options = || HtmlParserOptions { decode_entities: false, ..HtmlParserOptions::default() };
+    assert_eq!(
+        serialize_with_options("<p title='a &amp; b'>Fish &amp; Chips&nbsp;&lt;3</p>", options()),
+        "<p title=\"a &amp; b\">Fish &amp; Chips&nbsp;&lt;3</p>"
+    );
+
+    for name in ["entities.html", "attributes.html", "complex.html"] {
+        let source = fs::read_to_string(format!("tests/htmls/{}", name)).unwrap();
+        let serialized = serialize_with_options(&source, options());
+
+        let expected: Vec<String> = HtmlParser::from_str_with_options(&source, options()).map(|event| describe(event.unwrap())).collect();
+        let actual: Vec<String> = HtmlParser::from_str_with_options(&serialized, options()).map(|event| describe(event.unwrap())).collect();
+        assert_eq!(actual, expected, "{}", name);
+    }
+}
+
+#[test]
+fn escapes_text_of_foreign_script_elements() {
+    assert_eq!(
+        serialize("<svg><script>a &lt; b</script><style>&amp;</style></svg><script>a < b</script>"),
+        "<svg><script>a &lt; b</script><style>&amp;</style></svg><script>a < b</script>"
+    );
+}
+
+#[test]
+fn writes_dom_nodes() {
+    let document = Document::from_str("<!doctype html><ul><li>a &amp; b<li><img src=x.png alt=\"\"></ul>").unwrap();
+    let mut writer = HtmlWriter::new(vec![]);
+    writer.write_node(&document, document.root()).unwrap();
+
+    assert_eq!(
+        String::from_utf8(writer.into_inner()).unwrap(),
+        "<!DOCTYPE html><ul><li>a &amp; b<li><img src=\"x.png\" alt=\"\"></li></li></ul>"
+    );
+
+    let list = document.select("ul").unwrap()[0];
+    let mut writer = HtmlWriter::new(vec![]);
+    writer.write_node(&document, document.node(list).first_child().unwrap()).unwrap();
+
+    assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), "<li>a &amp; b<li><img src=\"x.png\" alt=\"\"></li></li>");
+}
+
+#[test]
+fn keeps_plaintext_and_comments_closed() {
+    let source = "<p>a</p><plaintext>b</plaintext><p>c";
+    let document = Document::from_str(source).unwrap();
+    let mut writer = HtmlWriter::new(vec![]);
+    writer.write_node(&document, document.root()).unwrap();
+
+    let serialized = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(serialized, "<p>a</p><plaintext>b</plaintext><p>c");
+    let expected: Vec<String> = HtmlParser::from_str(source).map(|event| describe(event.unwrap())).collect();
+    let actual: Vec<String> = HtmlParser::from_str(&serialized).map(|event| describe(event.unwrap())).collect();
+    assert_eq!(actual, expected);
+
+    let mut writer = HtmlWriter::new(vec![]);
+    for comment in ["a-->b", "a--!>b", ">a", "->a", "a->b", "a-"] {
+        writer.write_event(&HtmlEvent::Comment { content: Cow::Borrowed(comment), span: Span::default(), depth: 0 }).unwrap();
+    }
+
+    let serialized = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(serialized, "<!--a--&gt;b--><!--a--!&gt;b--><!--&gt;a--><!---&gt;a--><!--a->b--><!--a--->");
+    let comments: Vec<String> = HtmlParser::from_str(&serialized).map(|event| describe(event.unwrap())).collect();
+    assert_eq!(comments, vec!["comment a--&gt;b", "comment a--!&gt;b", "comment &gt;a", "comment -&gt;a", "comment a->b", "comment a-"]);
+}